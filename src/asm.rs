@@ -0,0 +1,310 @@
+use crate::cpu::{find_opcode, AddressingMode, Mnemonic};
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+use core::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AsmError {
+    UnknownMnemonic { line: usize, mnemonic: String },
+    InvalidOperand { line: usize, operand: String },
+    UnsupportedMode { line: usize, mnemonic: Mnemonic },
+    BranchOutOfRange { line: usize, target: u16 },
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AsmError::UnknownMnemonic { line, mnemonic } => {
+                write!(f, "line {}: unknown mnemonic {}", line, mnemonic)
+            }
+            AsmError::InvalidOperand { line, operand } => {
+                write!(f, "line {}: invalid operand {}", line, operand)
+            }
+            AsmError::UnsupportedMode { line, mnemonic } => write!(
+                f,
+                "line {}: {} does not support this addressing mode",
+                line, mnemonic
+            ),
+            AsmError::BranchOutOfRange { line, target } => {
+                write!(
+                    f,
+                    "line {}: branch target ${:04X} is out of range",
+                    line, target
+                )
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for AsmError {}
+
+enum Operand {
+    Implied,
+    Accumulator,
+    Immediate(u8),
+    /// `wide` forces the absolute form. It is set for addresses above 0xff
+    /// and for hex written with more than two digits, e.g. "$0010".
+    Address {
+        value: u16,
+        wide: bool,
+        index: Option<char>,
+    },
+    Indirect(u16),
+    IndirectX(u8),
+    IndirectY(u8),
+}
+
+/// Assembles `source` for a program loaded at 0x8000. See `assemble_at`.
+pub fn assemble(source: &str) -> Result<Vec<u8>, AsmError> {
+    assemble_at(source, 0x8000)
+}
+
+/// Assembles one instruction per line, e.g. "LDA #$05", "STA $10,X" or
+/// "BNE $8002". Numbers are hex with a `$` prefix or decimal, `;` starts a
+/// comment, and branch operands are absolute targets resolved against
+/// `origin`.
+pub fn assemble_at(source: &str, origin: u16) -> Result<Vec<u8>, AsmError> {
+    let mut program = Vec::new();
+
+    for (i, text) in source.lines().enumerate() {
+        let line = i + 1;
+        let text = text.split(';').next().unwrap_or("").trim();
+        if text.is_empty() {
+            continue;
+        }
+
+        let (name, operand_text) = match text.find(char::is_whitespace) {
+            Some(pos) => (&text[..pos], text[pos..].trim()),
+            None => (text, ""),
+        };
+        let mnemonic: Mnemonic = name.parse().map_err(|_| AsmError::UnknownMnemonic {
+            line,
+            mnemonic: name.to_string(),
+        })?;
+        let operand = parse_operand(operand_text).ok_or_else(|| AsmError::InvalidOperand {
+            line,
+            operand: operand_text.to_string(),
+        })?;
+
+        let (op, mode) = candidate_modes(&operand)
+            .iter()
+            .find_map(|&mode| find_opcode(mnemonic, mode).map(|op| (op, mode)))
+            .ok_or(AsmError::UnsupportedMode { line, mnemonic })?;
+
+        let pc = origin.wrapping_add(program.len() as u16);
+        program.push(op.opcode());
+        match (mode, operand) {
+            (AddressingMode::Relative, Operand::Address { value, .. }) => {
+                let offset = value.wrapping_sub(pc.wrapping_add(2)) as i16;
+                if !(-128..=127).contains(&offset) {
+                    return Err(AsmError::BranchOutOfRange {
+                        line,
+                        target: value,
+                    });
+                }
+                program.push(offset as u8);
+            }
+            (_, Operand::Immediate(value))
+            | (_, Operand::IndirectX(value))
+            | (_, Operand::IndirectY(value)) => program.push(value),
+            (_, Operand::Address { value, .. }) | (_, Operand::Indirect(value)) => {
+                if mode.operand_bytes() == 1 {
+                    program.push(value as u8);
+                } else {
+                    program.extend_from_slice(&value.to_le_bytes());
+                }
+            }
+            (_, Operand::Implied) | (_, Operand::Accumulator) => {}
+        }
+    }
+
+    Ok(program)
+}
+
+fn candidate_modes(operand: &Operand) -> &'static [AddressingMode] {
+    use AddressingMode::*;
+
+    match operand {
+        Operand::Implied => &[NoneAddressing, Accumulator],
+        Operand::Accumulator => &[Accumulator],
+        Operand::Immediate(_) => &[Immediate],
+        Operand::Address { wide, index, .. } => match (wide, index) {
+            (false, None) => &[Relative, ZeroPage, Absolute],
+            (true, None) => &[Relative, Absolute],
+            (false, Some('X')) => &[ZeroPageX, AbsoluteX],
+            (true, Some('X')) => &[AbsoluteX],
+            (false, Some(_)) => &[ZeroPageY, AbsoluteY],
+            (true, Some(_)) => &[AbsoluteY],
+        },
+        Operand::Indirect(_) => &[Indirect],
+        Operand::IndirectX(_) => &[IndirectX],
+        Operand::IndirectY(_) => &[IndirectY],
+    }
+}
+
+fn parse_operand(text: &str) -> Option<Operand> {
+    let text = text.to_ascii_uppercase().replace(' ', "");
+
+    if text.is_empty() {
+        return Some(Operand::Implied);
+    }
+    if text == "A" {
+        return Some(Operand::Accumulator);
+    }
+    if let Some(value) = text.strip_prefix('#') {
+        let (value, _) = parse_number(value)?;
+        return u8::try_from(value).ok().map(Operand::Immediate);
+    }
+    if let Some(inner) = text.strip_prefix('(') {
+        if let Some(value) = inner.strip_suffix(",X)") {
+            return parse_byte(value).map(Operand::IndirectX);
+        }
+        if let Some(value) = inner.strip_suffix("),Y") {
+            return parse_byte(value).map(Operand::IndirectY);
+        }
+        let (value, _) = parse_number(inner.strip_suffix(')')?)?;
+        return Some(Operand::Indirect(value));
+    }
+
+    let (address, index) = match text.strip_suffix(",X") {
+        Some(address) => (address, Some('X')),
+        None => match text.strip_suffix(",Y") {
+            Some(address) => (address, Some('Y')),
+            None => (text.as_str(), None),
+        },
+    };
+    let (value, wide) = parse_number(address)?;
+    Some(Operand::Address { value, wide, index })
+}
+
+fn parse_byte(text: &str) -> Option<u8> {
+    let (value, _) = parse_number(text)?;
+    u8::try_from(value).ok()
+}
+
+/// Parses `$hex` or decimal, also reporting whether the number needs the
+/// absolute form.
+fn parse_number(text: &str) -> Option<(u16, bool)> {
+    match text.strip_prefix('$') {
+        Some(hex) => {
+            let value = u16::from_str_radix(hex, 16).ok()?;
+            Some((value, hex.len() > 2))
+        }
+        None => {
+            let value: u16 = text.parse().ok()?;
+            Some((value, value > 0xff))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cpu::CPU;
+
+    #[test]
+    fn test_assemble() {
+        let program = assemble(
+            "
+            LDA #$05    ; load
+            STA $10
+            STA $0200,X
+            INX
+            ASL
+            BRK
+            ",
+        )
+        .unwrap();
+        assert_eq!(
+            program,
+            vec![0xa9, 0x05, 0x85, 0x10, 0x9d, 0x00, 0x02, 0xe8, 0x0a, 0x00]
+        );
+
+        let mut cpu = CPU::new();
+        cpu.load_and_run(program);
+        assert_eq!(cpu.register_a, 0x0a);
+        assert_eq!(cpu.register_x, 0x01);
+        assert_eq!(cpu.mem_read(0x10), 0x05);
+        assert_eq!(cpu.mem_read(0x0200), 0x05);
+    }
+
+    #[test]
+    fn test_assemble_loop() {
+        let program = assemble(
+            "ldx #3
+             lda #0
+             clc
+             adc #10
+             dex
+             bne $8004
+             brk",
+        )
+        .unwrap();
+
+        let mut cpu = CPU::new();
+        cpu.load_and_run(program);
+        assert_eq!(cpu.register_a, 30);
+        assert_eq!(cpu.register_x, 0);
+    }
+
+    #[test]
+    fn test_assemble_addressing_modes() {
+        let program = assemble_at(
+            "LDA $10,X
+             LDX $10,Y
+             LDA $10,Y
+             JMP $10
+             JMP ($1234)
+             LDA ($20,X)
+             LDA ($20),Y
+             ROR A",
+            0x0600,
+        )
+        .unwrap();
+        assert_eq!(
+            program,
+            vec![
+                0xb5, 0x10, 0xb6, 0x10, 0xb9, 0x10, 0x00, 0x4c, 0x10, 0x00, 0x6c, 0x34, 0x12, 0xa1,
+                0x20, 0xb1, 0x20, 0x6a,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_assemble_errors() {
+        assert_eq!(
+            assemble("INX\nFOO #$01"),
+            Err(AsmError::UnknownMnemonic {
+                line: 2,
+                mnemonic: "FOO".to_string()
+            })
+        );
+        assert_eq!(
+            assemble("LDA #$100"),
+            Err(AsmError::InvalidOperand {
+                line: 1,
+                operand: "#$100".to_string()
+            })
+        );
+        assert_eq!(
+            assemble("TAX #$01"),
+            Err(AsmError::UnsupportedMode {
+                line: 1,
+                mnemonic: Mnemonic::TAX
+            })
+        );
+        assert_eq!(
+            assemble("BNE $9000"),
+            Err(AsmError::BranchOutOfRange {
+                line: 1,
+                target: 0x9000
+            })
+        );
+        assert_eq!(
+            assemble("TAX #$01").unwrap_err().to_string(),
+            "line 1: TAX does not support this addressing mode"
+        );
+    }
+}
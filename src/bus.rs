@@ -0,0 +1,150 @@
+use alloc::boxed::Box;
+use alloc::vec;
+use core::convert::TryInto;
+
+pub(crate) const MEMORY_SIZE: usize = 0x10000;
+
+/// The CPU's view of the address space. Memory-mapped devices implement this
+/// to observe or intercept accesses.
+pub trait Bus {
+    fn read(&mut self, addr: u16) -> u8;
+    fn write(&mut self, addr: u16, data: u8);
+    /// Returns what `read` would, without side effects. Used to resolve
+    /// operand addresses and by debugging helpers such as `disassemble`.
+    fn peek(&self, addr: u16) -> u8;
+    /// Stores a byte without the side effects of `write`. Used by loaders
+    /// and debugging helpers such as `mem_write`.
+    fn poke(&mut self, addr: u16, data: u8);
+}
+
+/// 64KB of plain RAM.
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FlatMemory {
+    /// Whether 0x0000-0x07ff is mirrored up to 0x1fff like the NES internal
+    /// RAM. Off by default so test programs see a flat address space.
+    pub ram_mirroring: bool,
+
+    #[cfg_attr(feature = "serde", serde(with = "memory_serde"))]
+    memory: Box<[u8; MEMORY_SIZE]>,
+}
+
+impl FlatMemory {
+    pub fn new() -> Self {
+        Self {
+            ram_mirroring: false,
+            // Box::new([0; MEMORY_SIZE]) would build the array on the stack
+            // first in debug builds.
+            memory: vec![0; MEMORY_SIZE].into_boxed_slice().try_into().unwrap(),
+        }
+    }
+
+    fn mirror(&self, addr: u16) -> usize {
+        if self.ram_mirroring && addr < 0x2000 {
+            (addr & 0x07ff) as usize
+        } else {
+            addr as usize
+        }
+    }
+}
+
+impl Default for FlatMemory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Bus for FlatMemory {
+    fn read(&mut self, addr: u16) -> u8 {
+        self.peek(addr)
+    }
+
+    fn write(&mut self, addr: u16, data: u8) {
+        self.memory[self.mirror(addr)] = data;
+    }
+
+    fn peek(&self, addr: u16) -> u8 {
+        self.memory[self.mirror(addr)]
+    }
+
+    fn poke(&mut self, addr: u16, data: u8) {
+        self.write(addr, data);
+    }
+}
+
+// serde only derives arrays of up to 32 elements, so memory goes through a
+// byte buffer.
+#[cfg(feature = "serde")]
+mod memory_serde {
+    use super::MEMORY_SIZE;
+    use alloc::boxed::Box;
+    use alloc::vec::Vec;
+    use core::convert::TryInto;
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        memory: &[u8; MEMORY_SIZE],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(&memory[..])
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Box<[u8; MEMORY_SIZE]>, D::Error> {
+        let bytes = Vec::<u8>::deserialize(deserializer)?;
+        let len = bytes.len();
+        bytes
+            .into_boxed_slice()
+            .try_into()
+            .map_err(|_| D::Error::invalid_length(len, &"65536 bytes of memory"))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_read_write() {
+        let mut memory = FlatMemory::new();
+        memory.write(0x1234, 0x42);
+        assert_eq!(memory.read(0x1234), 0x42);
+        assert_eq!(memory.peek(0x1234), 0x42);
+        assert_eq!(memory.peek(0xffff), 0x00);
+
+        memory.poke(0xffff, 0x24);
+        assert_eq!(memory.read(0xffff), 0x24);
+    }
+
+    #[test]
+    fn test_memory_is_heap_allocated() {
+        assert!(core::mem::size_of::<FlatMemory>() < MEMORY_SIZE);
+
+        let mut memory = FlatMemory::new();
+        memory.write(0xffff, 0x42);
+        assert_eq!(memory.read(0xffff), 0x42);
+    }
+
+    #[test]
+    fn test_ram_mirroring() {
+        let mut memory = FlatMemory::new();
+        memory.ram_mirroring = true;
+        memory.write(0x0000, 0x42);
+        assert_eq!(memory.read(0x0800), 0x42);
+        assert_eq!(memory.read(0x1000), 0x42);
+        assert_eq!(memory.read(0x1800), 0x42);
+
+        memory.write(0x1fff, 0x24);
+        assert_eq!(memory.read(0x07ff), 0x24);
+        assert_eq!(memory.read(0x2000), 0x00);
+    }
+
+    #[test]
+    fn test_ram_mirroring_disabled() {
+        let mut memory = FlatMemory::new();
+        memory.write(0x0000, 0x42);
+        assert_eq!(memory.read(0x0800), 0x00);
+    }
+}
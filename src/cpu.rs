@@ -1,15 +1,177 @@
+use crate::bus::{Bus, FlatMemory, MEMORY_SIZE};
+use crate::rom::{Rom, RomError, PRG_ROM_PAGE_SIZE};
+use alloc::boxed::Box;
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
+use core::str::FromStr;
+#[cfg(feature = "std")]
+use std::io;
+#[cfg(feature = "std")]
+use std::path::Path;
+#[cfg(feature = "std")]
+use std::time::{Duration, Instant};
+
 #[derive(Debug)]
-pub struct CPU {
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CPU<B: Bus = FlatMemory> {
     pub register_a: u8,
     pub register_x: u8,
     pub register_y: u8,
+    /// Offset of the stack top within the stack page, see `stack_base`.
+    pub register_sp: u8,
     pub status: u8,
     pub program_counter: u16,
+    /// Whether ADC and SBC honor the DECIMAL flag. The NES variant of the
+    /// chip has no decimal mode.
+    pub decimal_enabled: bool,
+    /// Whether to stop with `StopReason::Trap` on an instruction that jumps
+    /// to itself, which test ROMs use to signal completion.
+    pub trap_self_loops: bool,
+    /// Address of the page the stack lives in. The real chip always uses
+    /// 0x0100, the default.
+    pub stack_base: u16,
+    pub bus: B,
 
-    memory: [u8; 0xffff],
+    cycles: usize,
+    instructions: usize,
+    nmi_pending: bool,
+    irq_pending: bool,
+    breakpoints: BTreeSet<u16>,
+    /// A breakpoint that was just reported, so resuming doesn't stop on it
+    /// again.
+    resumed_breakpoint: Option<u16>,
+    watchpoints: BTreeMap<u16, Watchpoint>,
+    watchpoint_hit: Option<StopReason>,
+    /// Set by `jump` so `step` knows not to advance past the operand.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    jumped: bool,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    hooks: Hooks,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    ticks: TickState,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    trace_capacity: usize,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    trace_log: Vec<TraceEntry>,
 }
 
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct Watchpoint {
+    read: bool,
+    write: bool,
+}
+
+/// Progress through the instruction `tick` is clocking.
+#[derive(Debug, Default)]
+struct TickState {
+    /// Cycles the instruction takes before its effects land.
+    base: u8,
+    elapsed: u8,
+    /// Penalty cycles still owed after the instruction ran.
+    idle: u8,
+}
+
+type ReadHook = Box<dyn FnMut(u16) -> u8>;
+type WriteHook = Box<dyn FnMut(u16, u8)>;
+
+#[derive(Default)]
+struct Hooks {
+    reads: Vec<(u16, ReadHook)>,
+    writes: Vec<(u16, WriteHook)>,
+}
+
+impl fmt::Debug for Hooks {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Hooks")
+            .field("reads", &self.reads.len())
+            .field("writes", &self.writes.len())
+            .finish()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum StopReason {
+    /// A BRK was executed.
+    Break,
+    /// The byte at the program counter is not a known opcode.
+    UnknownOpcode(u8),
+    /// The program counter ran off the end of memory: either the operand of
+    /// the next instruction would extend past 0xffff, or the last instruction
+    /// ended there without jumping. In the latter case it did execute, and
+    /// the program counter has wrapped to 0x0000.
+    Halt,
+    /// The budget given to `run_for` was used up.
+    CyclesExhausted,
+    /// The program counter reached a breakpoint. The instruction there has
+    /// not run yet.
+    Breakpoint(u16),
+    /// The last instruction or interrupt entry accessed a watched address.
+    /// Reported in place of `Break` or `Trap` for the same instruction.
+    Watchpoint { addr: u16, write: bool },
+    /// The instruction at this address jumped to itself; see
+    /// `CPU::trap_self_loops`.
+    Trap(u16),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LoadError {
+    TooLarge { addr: u16, len: usize },
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoadError::TooLarge { addr, len } => write!(
+                f,
+                "program of {} bytes does not fit at {:#06x}: only {} bytes available",
+                len,
+                addr,
+                MEMORY_SIZE - *addr as usize
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for LoadError {}
+
+/// An executed instruction, with the registers as they were before it ran.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceEntry {
+    pub addr: u16,
+    pub opcode: u8,
+    pub disassembly: String,
+    pub register_a: u8,
+    pub register_x: u8,
+    pub register_y: u8,
+    pub register_sp: u8,
+    pub status: u8,
+}
+
+/// The instruction at the program counter, as returned by
+/// `CPU::peek_instruction`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodedInstruction {
+    pub mnemonic: Mnemonic,
+    pub addr_mode: AddressingMode,
+    /// The bytes following the opcode, low byte first.
+    pub operand: Vec<u8>,
+    /// The instruction as `disassemble` renders it, e.g. "LDA #$05".
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StepResult {
+    pub stop: Option<StopReason>,
+    pub cycles: u8,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum AddressingMode {
     Immediate,
     ZeroPage,
@@ -18,11 +180,27 @@ pub enum AddressingMode {
     Absolute,
     AbsoluteX,
     AbsoluteY,
+    Indirect,
     IndirectX,
     IndirectY,
+    Relative,
+    Accumulator,
     NoneAddressing,
 }
 
+impl AddressingMode {
+    /// Number of operand bytes following the opcode.
+    pub fn operand_bytes(&self) -> u8 {
+        use self::AddressingMode::*;
+
+        match self {
+            Accumulator | NoneAddressing => 0,
+            Immediate | ZeroPage | ZeroPageX | ZeroPageY | IndirectX | IndirectY | Relative => 1,
+            Absolute | AbsoluteX | AbsoluteY | Indirect => 2,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct OpCode {
     opcode: u8,
@@ -33,7 +211,7 @@ pub struct OpCode {
 }
 
 impl OpCode {
-    const fn new(
+    pub const fn new(
         opcode: u8,
         mnemonic: Mnemonic,
         bytes: u8,
@@ -48,17 +226,205 @@ impl OpCode {
             addr_mode,
         }
     }
+
+    pub fn opcode(&self) -> u8 {
+        self.opcode
+    }
+
+    pub fn mnemonic(&self) -> Mnemonic {
+        self.mnemonic
+    }
+
+    /// Instruction length, including the opcode byte.
+    pub fn bytes(&self) -> u8 {
+        self.bytes
+    }
+
+    /// Base cycle count, without page-crossing or branch penalties.
+    pub fn cycles(&self) -> u8 {
+        self.cycles
+    }
+
+    pub fn addr_mode(&self) -> AddressingMode {
+        self.addr_mode
+    }
+
+    fn is_unofficial(&self) -> bool {
+        use self::Mnemonic::*;
+
+        match self.mnemonic {
+            NOP => self.opcode != 0xea,
+            DCP | ISC | LAX | RLA | RRA | SAX | SLO | SRE => true,
+            _ => false,
+        }
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Mnemonic {
     ADC,
+    AND,
+    ASL,
+    BCC,
+    BCS,
+    BEQ,
+    BIT,
+    BMI,
+    BNE,
+    BPL,
     BRK,
+    BVC,
+    BVS,
+    CLC,
+    CLD,
+    CLI,
+    CLV,
+    CMP,
+    CPX,
+    CPY,
+    DCP,
+    DEC,
+    DEX,
+    DEY,
+    EOR,
+    INC,
     INX,
+    INY,
+    ISC,
+    JMP,
+    LAX,
     LDA,
+    LDX,
+    LDY,
+    LSR,
+    NOP,
+    ORA,
+    PHA,
+    PHP,
+    PLA,
+    PLP,
+    RLA,
+    ROL,
+    ROR,
+    RRA,
+    RTI,
+    SAX,
     SBC,
+    SEC,
+    SED,
+    SEI,
+    SLO,
+    SRE,
     STA,
+    STX,
+    STY,
     TAX,
+    TAY,
+    TSX,
+    TXA,
+    TXS,
+    TYA,
+}
+
+impl fmt::Display for Mnemonic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseMnemonicError(String);
+
+impl fmt::Display for ParseMnemonicError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "unknown mnemonic: {}", self.0)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ParseMnemonicError {}
+
+impl FromStr for Mnemonic {
+    type Err = ParseMnemonicError;
+
+    /// Parses a mnemonic case-insensitively, e.g. "lda" or "LDA".
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use self::Mnemonic::*;
+
+        let mut upper = [0; 3];
+        if s.len() != upper.len() {
+            return Err(ParseMnemonicError(s.to_string()));
+        }
+        for (dst, byte) in upper.iter_mut().zip(s.bytes()) {
+            *dst = byte.to_ascii_uppercase();
+        }
+
+        Ok(match &upper {
+            b"ADC" => ADC,
+            b"AND" => AND,
+            b"ASL" => ASL,
+            b"BCC" => BCC,
+            b"BCS" => BCS,
+            b"BEQ" => BEQ,
+            b"BIT" => BIT,
+            b"BMI" => BMI,
+            b"BNE" => BNE,
+            b"BPL" => BPL,
+            b"BRK" => BRK,
+            b"BVC" => BVC,
+            b"BVS" => BVS,
+            b"CLC" => CLC,
+            b"CLD" => CLD,
+            b"CLI" => CLI,
+            b"CLV" => CLV,
+            b"CMP" => CMP,
+            b"CPX" => CPX,
+            b"CPY" => CPY,
+            b"DCP" => DCP,
+            b"DEC" => DEC,
+            b"DEX" => DEX,
+            b"DEY" => DEY,
+            b"EOR" => EOR,
+            b"INC" => INC,
+            b"INX" => INX,
+            b"INY" => INY,
+            b"ISC" => ISC,
+            b"JMP" => JMP,
+            b"LAX" => LAX,
+            b"LDA" => LDA,
+            b"LDX" => LDX,
+            b"LDY" => LDY,
+            b"LSR" => LSR,
+            b"NOP" => NOP,
+            b"ORA" => ORA,
+            b"PHA" => PHA,
+            b"PHP" => PHP,
+            b"PLA" => PLA,
+            b"PLP" => PLP,
+            b"RLA" => RLA,
+            b"ROL" => ROL,
+            b"ROR" => ROR,
+            b"RRA" => RRA,
+            b"RTI" => RTI,
+            b"SAX" => SAX,
+            b"SBC" => SBC,
+            b"SEC" => SEC,
+            b"SED" => SED,
+            b"SEI" => SEI,
+            b"SLO" => SLO,
+            b"SRE" => SRE,
+            b"STA" => STA,
+            b"STX" => STX,
+            b"STY" => STY,
+            b"TAX" => TAX,
+            b"TAY" => TAY,
+            b"TSX" => TSX,
+            b"TXA" => TXA,
+            b"TXS" => TXS,
+            b"TYA" => TYA,
+            _ => return Err(ParseMnemonicError(s.to_string())),
+        })
+    }
 }
 
 pub const CPU_OPCODES: &[OpCode] = &[
@@ -70,8 +436,90 @@ pub const CPU_OPCODES: &[OpCode] = &[
     OpCode::new(0x79, Mnemonic::ADC, 3, 4, AddressingMode::AbsoluteY),
     OpCode::new(0x61, Mnemonic::ADC, 2, 6, AddressingMode::IndirectX),
     OpCode::new(0x71, Mnemonic::ADC, 2, 5, AddressingMode::IndirectY),
+    OpCode::new(0x29, Mnemonic::AND, 2, 2, AddressingMode::Immediate),
+    OpCode::new(0x25, Mnemonic::AND, 2, 3, AddressingMode::ZeroPage),
+    OpCode::new(0x35, Mnemonic::AND, 2, 4, AddressingMode::ZeroPageX),
+    OpCode::new(0x2d, Mnemonic::AND, 3, 4, AddressingMode::Absolute),
+    OpCode::new(0x3d, Mnemonic::AND, 3, 4, AddressingMode::AbsoluteX),
+    OpCode::new(0x39, Mnemonic::AND, 3, 4, AddressingMode::AbsoluteY),
+    OpCode::new(0x21, Mnemonic::AND, 2, 6, AddressingMode::IndirectX),
+    OpCode::new(0x31, Mnemonic::AND, 2, 5, AddressingMode::IndirectY),
+    OpCode::new(0x0a, Mnemonic::ASL, 1, 2, AddressingMode::Accumulator),
+    OpCode::new(0x06, Mnemonic::ASL, 2, 5, AddressingMode::ZeroPage),
+    OpCode::new(0x16, Mnemonic::ASL, 2, 6, AddressingMode::ZeroPageX),
+    OpCode::new(0x0e, Mnemonic::ASL, 3, 6, AddressingMode::Absolute),
+    OpCode::new(0x1e, Mnemonic::ASL, 3, 7, AddressingMode::AbsoluteX),
+    OpCode::new(0x90, Mnemonic::BCC, 2, 2, AddressingMode::Relative),
+    OpCode::new(0xb0, Mnemonic::BCS, 2, 2, AddressingMode::Relative),
+    OpCode::new(0xf0, Mnemonic::BEQ, 2, 2, AddressingMode::Relative),
+    OpCode::new(0x24, Mnemonic::BIT, 2, 3, AddressingMode::ZeroPage),
+    OpCode::new(0x2c, Mnemonic::BIT, 3, 4, AddressingMode::Absolute),
+    OpCode::new(0x30, Mnemonic::BMI, 2, 2, AddressingMode::Relative),
+    OpCode::new(0xd0, Mnemonic::BNE, 2, 2, AddressingMode::Relative),
+    OpCode::new(0x10, Mnemonic::BPL, 2, 2, AddressingMode::Relative),
     OpCode::new(0x00, Mnemonic::BRK, 1, 7, AddressingMode::NoneAddressing),
+    OpCode::new(0x50, Mnemonic::BVC, 2, 2, AddressingMode::Relative),
+    OpCode::new(0x70, Mnemonic::BVS, 2, 2, AddressingMode::Relative),
+    OpCode::new(0x18, Mnemonic::CLC, 1, 2, AddressingMode::NoneAddressing),
+    OpCode::new(0xd8, Mnemonic::CLD, 1, 2, AddressingMode::NoneAddressing),
+    OpCode::new(0x58, Mnemonic::CLI, 1, 2, AddressingMode::NoneAddressing),
+    OpCode::new(0xb8, Mnemonic::CLV, 1, 2, AddressingMode::NoneAddressing),
+    OpCode::new(0xc9, Mnemonic::CMP, 2, 2, AddressingMode::Immediate),
+    OpCode::new(0xc5, Mnemonic::CMP, 2, 3, AddressingMode::ZeroPage),
+    OpCode::new(0xd5, Mnemonic::CMP, 2, 4, AddressingMode::ZeroPageX),
+    OpCode::new(0xcd, Mnemonic::CMP, 3, 4, AddressingMode::Absolute),
+    OpCode::new(0xdd, Mnemonic::CMP, 3, 4, AddressingMode::AbsoluteX),
+    OpCode::new(0xd9, Mnemonic::CMP, 3, 4, AddressingMode::AbsoluteY),
+    OpCode::new(0xc1, Mnemonic::CMP, 2, 6, AddressingMode::IndirectX),
+    OpCode::new(0xd1, Mnemonic::CMP, 2, 5, AddressingMode::IndirectY),
+    OpCode::new(0xe0, Mnemonic::CPX, 2, 2, AddressingMode::Immediate),
+    OpCode::new(0xe4, Mnemonic::CPX, 2, 3, AddressingMode::ZeroPage),
+    OpCode::new(0xec, Mnemonic::CPX, 3, 4, AddressingMode::Absolute),
+    OpCode::new(0xc0, Mnemonic::CPY, 2, 2, AddressingMode::Immediate),
+    OpCode::new(0xc4, Mnemonic::CPY, 2, 3, AddressingMode::ZeroPage),
+    OpCode::new(0xcc, Mnemonic::CPY, 3, 4, AddressingMode::Absolute),
+    OpCode::new(0xc7, Mnemonic::DCP, 2, 5, AddressingMode::ZeroPage),
+    OpCode::new(0xd7, Mnemonic::DCP, 2, 6, AddressingMode::ZeroPageX),
+    OpCode::new(0xcf, Mnemonic::DCP, 3, 6, AddressingMode::Absolute),
+    OpCode::new(0xdf, Mnemonic::DCP, 3, 7, AddressingMode::AbsoluteX),
+    OpCode::new(0xdb, Mnemonic::DCP, 3, 7, AddressingMode::AbsoluteY),
+    OpCode::new(0xc3, Mnemonic::DCP, 2, 8, AddressingMode::IndirectX),
+    OpCode::new(0xd3, Mnemonic::DCP, 2, 8, AddressingMode::IndirectY),
+    OpCode::new(0xc6, Mnemonic::DEC, 2, 5, AddressingMode::ZeroPage),
+    OpCode::new(0xd6, Mnemonic::DEC, 2, 6, AddressingMode::ZeroPageX),
+    OpCode::new(0xce, Mnemonic::DEC, 3, 6, AddressingMode::Absolute),
+    OpCode::new(0xde, Mnemonic::DEC, 3, 7, AddressingMode::AbsoluteX),
+    OpCode::new(0xca, Mnemonic::DEX, 1, 2, AddressingMode::NoneAddressing),
+    OpCode::new(0x88, Mnemonic::DEY, 1, 2, AddressingMode::NoneAddressing),
+    OpCode::new(0x49, Mnemonic::EOR, 2, 2, AddressingMode::Immediate),
+    OpCode::new(0x45, Mnemonic::EOR, 2, 3, AddressingMode::ZeroPage),
+    OpCode::new(0x55, Mnemonic::EOR, 2, 4, AddressingMode::ZeroPageX),
+    OpCode::new(0x4d, Mnemonic::EOR, 3, 4, AddressingMode::Absolute),
+    OpCode::new(0x5d, Mnemonic::EOR, 3, 4, AddressingMode::AbsoluteX),
+    OpCode::new(0x59, Mnemonic::EOR, 3, 4, AddressingMode::AbsoluteY),
+    OpCode::new(0x41, Mnemonic::EOR, 2, 6, AddressingMode::IndirectX),
+    OpCode::new(0x51, Mnemonic::EOR, 2, 5, AddressingMode::IndirectY),
+    OpCode::new(0xe6, Mnemonic::INC, 2, 5, AddressingMode::ZeroPage),
+    OpCode::new(0xf6, Mnemonic::INC, 2, 6, AddressingMode::ZeroPageX),
+    OpCode::new(0xee, Mnemonic::INC, 3, 6, AddressingMode::Absolute),
+    OpCode::new(0xfe, Mnemonic::INC, 3, 7, AddressingMode::AbsoluteX),
     OpCode::new(0xe8, Mnemonic::INX, 1, 2, AddressingMode::NoneAddressing),
+    OpCode::new(0xc8, Mnemonic::INY, 1, 2, AddressingMode::NoneAddressing),
+    OpCode::new(0xe7, Mnemonic::ISC, 2, 5, AddressingMode::ZeroPage),
+    OpCode::new(0xf7, Mnemonic::ISC, 2, 6, AddressingMode::ZeroPageX),
+    OpCode::new(0xef, Mnemonic::ISC, 3, 6, AddressingMode::Absolute),
+    OpCode::new(0xff, Mnemonic::ISC, 3, 7, AddressingMode::AbsoluteX),
+    OpCode::new(0xfb, Mnemonic::ISC, 3, 7, AddressingMode::AbsoluteY),
+    OpCode::new(0xe3, Mnemonic::ISC, 2, 8, AddressingMode::IndirectX),
+    OpCode::new(0xf3, Mnemonic::ISC, 2, 8, AddressingMode::IndirectY),
+    OpCode::new(0x4c, Mnemonic::JMP, 3, 3, AddressingMode::Absolute),
+    OpCode::new(0x6c, Mnemonic::JMP, 3, 5, AddressingMode::Indirect),
+    OpCode::new(0xa7, Mnemonic::LAX, 2, 3, AddressingMode::ZeroPage),
+    OpCode::new(0xb7, Mnemonic::LAX, 2, 4, AddressingMode::ZeroPageY),
+    OpCode::new(0xaf, Mnemonic::LAX, 3, 4, AddressingMode::Absolute),
+    OpCode::new(0xbf, Mnemonic::LAX, 3, 4, AddressingMode::AbsoluteY),
+    OpCode::new(0xa3, Mnemonic::LAX, 2, 6, AddressingMode::IndirectX),
+    OpCode::new(0xb3, Mnemonic::LAX, 2, 5, AddressingMode::IndirectY),
     OpCode::new(0xa9, Mnemonic::LDA, 2, 2, AddressingMode::Immediate),
     OpCode::new(0xa5, Mnemonic::LDA, 2, 3, AddressingMode::ZeroPage),
     OpCode::new(0xb5, Mnemonic::LDA, 2, 4, AddressingMode::ZeroPageX),
@@ -80,6 +528,90 @@ pub const CPU_OPCODES: &[OpCode] = &[
     OpCode::new(0xb9, Mnemonic::LDA, 3, 4, AddressingMode::AbsoluteY),
     OpCode::new(0xa1, Mnemonic::LDA, 2, 6, AddressingMode::IndirectX),
     OpCode::new(0xb1, Mnemonic::LDA, 2, 5, AddressingMode::IndirectY),
+    OpCode::new(0xa2, Mnemonic::LDX, 2, 2, AddressingMode::Immediate),
+    OpCode::new(0xa6, Mnemonic::LDX, 2, 3, AddressingMode::ZeroPage),
+    OpCode::new(0xb6, Mnemonic::LDX, 2, 4, AddressingMode::ZeroPageY),
+    OpCode::new(0xae, Mnemonic::LDX, 3, 4, AddressingMode::Absolute),
+    OpCode::new(0xbe, Mnemonic::LDX, 3, 4, AddressingMode::AbsoluteY),
+    OpCode::new(0xa0, Mnemonic::LDY, 2, 2, AddressingMode::Immediate),
+    OpCode::new(0xa4, Mnemonic::LDY, 2, 3, AddressingMode::ZeroPage),
+    OpCode::new(0xb4, Mnemonic::LDY, 2, 4, AddressingMode::ZeroPageX),
+    OpCode::new(0xac, Mnemonic::LDY, 3, 4, AddressingMode::Absolute),
+    OpCode::new(0xbc, Mnemonic::LDY, 3, 4, AddressingMode::AbsoluteX),
+    OpCode::new(0x4a, Mnemonic::LSR, 1, 2, AddressingMode::Accumulator),
+    OpCode::new(0x46, Mnemonic::LSR, 2, 5, AddressingMode::ZeroPage),
+    OpCode::new(0x56, Mnemonic::LSR, 2, 6, AddressingMode::ZeroPageX),
+    OpCode::new(0x4e, Mnemonic::LSR, 3, 6, AddressingMode::Absolute),
+    OpCode::new(0x5e, Mnemonic::LSR, 3, 7, AddressingMode::AbsoluteX),
+    OpCode::new(0xea, Mnemonic::NOP, 1, 2, AddressingMode::NoneAddressing),
+    OpCode::new(0x1a, Mnemonic::NOP, 1, 2, AddressingMode::NoneAddressing),
+    OpCode::new(0x3a, Mnemonic::NOP, 1, 2, AddressingMode::NoneAddressing),
+    OpCode::new(0x5a, Mnemonic::NOP, 1, 2, AddressingMode::NoneAddressing),
+    OpCode::new(0x7a, Mnemonic::NOP, 1, 2, AddressingMode::NoneAddressing),
+    OpCode::new(0xda, Mnemonic::NOP, 1, 2, AddressingMode::NoneAddressing),
+    OpCode::new(0xfa, Mnemonic::NOP, 1, 2, AddressingMode::NoneAddressing),
+    OpCode::new(0x80, Mnemonic::NOP, 2, 2, AddressingMode::Immediate),
+    OpCode::new(0x82, Mnemonic::NOP, 2, 2, AddressingMode::Immediate),
+    OpCode::new(0x89, Mnemonic::NOP, 2, 2, AddressingMode::Immediate),
+    OpCode::new(0xc2, Mnemonic::NOP, 2, 2, AddressingMode::Immediate),
+    OpCode::new(0xe2, Mnemonic::NOP, 2, 2, AddressingMode::Immediate),
+    OpCode::new(0x04, Mnemonic::NOP, 2, 3, AddressingMode::ZeroPage),
+    OpCode::new(0x44, Mnemonic::NOP, 2, 3, AddressingMode::ZeroPage),
+    OpCode::new(0x64, Mnemonic::NOP, 2, 3, AddressingMode::ZeroPage),
+    OpCode::new(0x14, Mnemonic::NOP, 2, 4, AddressingMode::ZeroPageX),
+    OpCode::new(0x34, Mnemonic::NOP, 2, 4, AddressingMode::ZeroPageX),
+    OpCode::new(0x54, Mnemonic::NOP, 2, 4, AddressingMode::ZeroPageX),
+    OpCode::new(0x74, Mnemonic::NOP, 2, 4, AddressingMode::ZeroPageX),
+    OpCode::new(0xd4, Mnemonic::NOP, 2, 4, AddressingMode::ZeroPageX),
+    OpCode::new(0xf4, Mnemonic::NOP, 2, 4, AddressingMode::ZeroPageX),
+    OpCode::new(0x0c, Mnemonic::NOP, 3, 4, AddressingMode::Absolute),
+    OpCode::new(0x1c, Mnemonic::NOP, 3, 4, AddressingMode::AbsoluteX),
+    OpCode::new(0x3c, Mnemonic::NOP, 3, 4, AddressingMode::AbsoluteX),
+    OpCode::new(0x5c, Mnemonic::NOP, 3, 4, AddressingMode::AbsoluteX),
+    OpCode::new(0x7c, Mnemonic::NOP, 3, 4, AddressingMode::AbsoluteX),
+    OpCode::new(0xdc, Mnemonic::NOP, 3, 4, AddressingMode::AbsoluteX),
+    OpCode::new(0xfc, Mnemonic::NOP, 3, 4, AddressingMode::AbsoluteX),
+    OpCode::new(0x09, Mnemonic::ORA, 2, 2, AddressingMode::Immediate),
+    OpCode::new(0x05, Mnemonic::ORA, 2, 3, AddressingMode::ZeroPage),
+    OpCode::new(0x15, Mnemonic::ORA, 2, 4, AddressingMode::ZeroPageX),
+    OpCode::new(0x0d, Mnemonic::ORA, 3, 4, AddressingMode::Absolute),
+    OpCode::new(0x1d, Mnemonic::ORA, 3, 4, AddressingMode::AbsoluteX),
+    OpCode::new(0x19, Mnemonic::ORA, 3, 4, AddressingMode::AbsoluteY),
+    OpCode::new(0x01, Mnemonic::ORA, 2, 6, AddressingMode::IndirectX),
+    OpCode::new(0x11, Mnemonic::ORA, 2, 5, AddressingMode::IndirectY),
+    OpCode::new(0x48, Mnemonic::PHA, 1, 3, AddressingMode::NoneAddressing),
+    OpCode::new(0x08, Mnemonic::PHP, 1, 3, AddressingMode::NoneAddressing),
+    OpCode::new(0x68, Mnemonic::PLA, 1, 4, AddressingMode::NoneAddressing),
+    OpCode::new(0x28, Mnemonic::PLP, 1, 4, AddressingMode::NoneAddressing),
+    OpCode::new(0x27, Mnemonic::RLA, 2, 5, AddressingMode::ZeroPage),
+    OpCode::new(0x37, Mnemonic::RLA, 2, 6, AddressingMode::ZeroPageX),
+    OpCode::new(0x2f, Mnemonic::RLA, 3, 6, AddressingMode::Absolute),
+    OpCode::new(0x3f, Mnemonic::RLA, 3, 7, AddressingMode::AbsoluteX),
+    OpCode::new(0x3b, Mnemonic::RLA, 3, 7, AddressingMode::AbsoluteY),
+    OpCode::new(0x23, Mnemonic::RLA, 2, 8, AddressingMode::IndirectX),
+    OpCode::new(0x33, Mnemonic::RLA, 2, 8, AddressingMode::IndirectY),
+    OpCode::new(0x2a, Mnemonic::ROL, 1, 2, AddressingMode::Accumulator),
+    OpCode::new(0x26, Mnemonic::ROL, 2, 5, AddressingMode::ZeroPage),
+    OpCode::new(0x36, Mnemonic::ROL, 2, 6, AddressingMode::ZeroPageX),
+    OpCode::new(0x2e, Mnemonic::ROL, 3, 6, AddressingMode::Absolute),
+    OpCode::new(0x3e, Mnemonic::ROL, 3, 7, AddressingMode::AbsoluteX),
+    OpCode::new(0x6a, Mnemonic::ROR, 1, 2, AddressingMode::Accumulator),
+    OpCode::new(0x66, Mnemonic::ROR, 2, 5, AddressingMode::ZeroPage),
+    OpCode::new(0x76, Mnemonic::ROR, 2, 6, AddressingMode::ZeroPageX),
+    OpCode::new(0x6e, Mnemonic::ROR, 3, 6, AddressingMode::Absolute),
+    OpCode::new(0x7e, Mnemonic::ROR, 3, 7, AddressingMode::AbsoluteX),
+    OpCode::new(0x67, Mnemonic::RRA, 2, 5, AddressingMode::ZeroPage),
+    OpCode::new(0x77, Mnemonic::RRA, 2, 6, AddressingMode::ZeroPageX),
+    OpCode::new(0x6f, Mnemonic::RRA, 3, 6, AddressingMode::Absolute),
+    OpCode::new(0x7f, Mnemonic::RRA, 3, 7, AddressingMode::AbsoluteX),
+    OpCode::new(0x7b, Mnemonic::RRA, 3, 7, AddressingMode::AbsoluteY),
+    OpCode::new(0x63, Mnemonic::RRA, 2, 8, AddressingMode::IndirectX),
+    OpCode::new(0x73, Mnemonic::RRA, 2, 8, AddressingMode::IndirectY),
+    OpCode::new(0x40, Mnemonic::RTI, 1, 6, AddressingMode::NoneAddressing),
+    OpCode::new(0x87, Mnemonic::SAX, 2, 3, AddressingMode::ZeroPage),
+    OpCode::new(0x97, Mnemonic::SAX, 2, 4, AddressingMode::ZeroPageY),
+    OpCode::new(0x8f, Mnemonic::SAX, 3, 4, AddressingMode::Absolute),
+    OpCode::new(0x83, Mnemonic::SAX, 2, 6, AddressingMode::IndirectX),
     OpCode::new(0xe9, Mnemonic::SBC, 2, 2, AddressingMode::Immediate),
     OpCode::new(0xe5, Mnemonic::SBC, 2, 3, AddressingMode::ZeroPage),
     OpCode::new(0xf5, Mnemonic::SBC, 2, 4, AddressingMode::ZeroPageX),
@@ -88,6 +620,23 @@ pub const CPU_OPCODES: &[OpCode] = &[
     OpCode::new(0xf9, Mnemonic::SBC, 3, 4, AddressingMode::AbsoluteY),
     OpCode::new(0xe1, Mnemonic::SBC, 2, 6, AddressingMode::IndirectX),
     OpCode::new(0xf1, Mnemonic::SBC, 2, 5, AddressingMode::IndirectY),
+    OpCode::new(0x38, Mnemonic::SEC, 1, 2, AddressingMode::NoneAddressing),
+    OpCode::new(0xf8, Mnemonic::SED, 1, 2, AddressingMode::NoneAddressing),
+    OpCode::new(0x78, Mnemonic::SEI, 1, 2, AddressingMode::NoneAddressing),
+    OpCode::new(0x07, Mnemonic::SLO, 2, 5, AddressingMode::ZeroPage),
+    OpCode::new(0x17, Mnemonic::SLO, 2, 6, AddressingMode::ZeroPageX),
+    OpCode::new(0x0f, Mnemonic::SLO, 3, 6, AddressingMode::Absolute),
+    OpCode::new(0x1f, Mnemonic::SLO, 3, 7, AddressingMode::AbsoluteX),
+    OpCode::new(0x1b, Mnemonic::SLO, 3, 7, AddressingMode::AbsoluteY),
+    OpCode::new(0x03, Mnemonic::SLO, 2, 8, AddressingMode::IndirectX),
+    OpCode::new(0x13, Mnemonic::SLO, 2, 8, AddressingMode::IndirectY),
+    OpCode::new(0x47, Mnemonic::SRE, 2, 5, AddressingMode::ZeroPage),
+    OpCode::new(0x57, Mnemonic::SRE, 2, 6, AddressingMode::ZeroPageX),
+    OpCode::new(0x4f, Mnemonic::SRE, 3, 6, AddressingMode::Absolute),
+    OpCode::new(0x5f, Mnemonic::SRE, 3, 7, AddressingMode::AbsoluteX),
+    OpCode::new(0x5b, Mnemonic::SRE, 3, 7, AddressingMode::AbsoluteY),
+    OpCode::new(0x43, Mnemonic::SRE, 2, 8, AddressingMode::IndirectX),
+    OpCode::new(0x53, Mnemonic::SRE, 2, 8, AddressingMode::IndirectY),
     OpCode::new(0x85, Mnemonic::STA, 2, 3, AddressingMode::ZeroPage),
     OpCode::new(0x95, Mnemonic::STA, 2, 4, AddressingMode::ZeroPageX),
     OpCode::new(0x8d, Mnemonic::STA, 3, 4, AddressingMode::Absolute),
@@ -95,274 +644,1780 @@ pub const CPU_OPCODES: &[OpCode] = &[
     OpCode::new(0x99, Mnemonic::STA, 3, 5, AddressingMode::AbsoluteY),
     OpCode::new(0x81, Mnemonic::STA, 2, 6, AddressingMode::IndirectX),
     OpCode::new(0x91, Mnemonic::STA, 2, 6, AddressingMode::IndirectY),
+    OpCode::new(0x86, Mnemonic::STX, 2, 3, AddressingMode::ZeroPage),
+    OpCode::new(0x96, Mnemonic::STX, 2, 4, AddressingMode::ZeroPageY),
+    OpCode::new(0x8e, Mnemonic::STX, 3, 4, AddressingMode::Absolute),
+    OpCode::new(0x84, Mnemonic::STY, 2, 3, AddressingMode::ZeroPage),
+    OpCode::new(0x94, Mnemonic::STY, 2, 4, AddressingMode::ZeroPageX),
+    OpCode::new(0x8c, Mnemonic::STY, 3, 4, AddressingMode::Absolute),
     OpCode::new(0xaa, Mnemonic::TAX, 1, 2, AddressingMode::NoneAddressing),
+    OpCode::new(0xa8, Mnemonic::TAY, 1, 2, AddressingMode::NoneAddressing),
+    OpCode::new(0xba, Mnemonic::TSX, 1, 2, AddressingMode::NoneAddressing),
+    OpCode::new(0x8a, Mnemonic::TXA, 1, 2, AddressingMode::NoneAddressing),
+    OpCode::new(0x9a, Mnemonic::TXS, 1, 2, AddressingMode::NoneAddressing),
+    OpCode::new(0x98, Mnemonic::TYA, 1, 2, AddressingMode::NoneAddressing),
 ];
 
+/// Finds the opcode encoding `mnemonic` with `mode`, preferring the official
+/// one when several exist.
+pub fn find_opcode(mnemonic: Mnemonic, mode: AddressingMode) -> Option<&'static OpCode> {
+    CPU_OPCODES
+        .iter()
+        .filter(|op| op.mnemonic == mnemonic && op.addr_mode == mode)
+        .min_by_key(|op| op.is_unofficial())
+}
+
+/// Returns the opcode bytes this CPU can execute, in ascending order.
+pub fn implemented_opcodes() -> Vec<u8> {
+    (0..=0xff)
+        .filter(|&opcode| OPCODE_TABLE[opcode as usize].is_some())
+        .collect()
+}
+
+/// Returns the fraction of the 256 possible opcodes that are implemented.
+pub fn coverage() -> f32 {
+    implemented_opcodes().len() as f32 / 256.0
+}
+
+/// Checks that no two `CPU_OPCODES` entries share an opcode byte, returning
+/// the duplicated bytes otherwise. The decode table keeps only the last entry
+/// for each byte, so a duplicate silently shadows an instruction.
+pub fn validate_opcode_table() -> Result<(), Vec<u8>> {
+    let mut seen = [false; 256];
+    let mut duplicates = BTreeSet::new();
+    for op in CPU_OPCODES {
+        if seen[op.opcode as usize] {
+            duplicates.insert(op.opcode);
+        }
+        seen[op.opcode as usize] = true;
+    }
+
+    if duplicates.is_empty() {
+        Ok(())
+    } else {
+        Err(duplicates.into_iter().collect())
+    }
+}
+
+static OPCODE_TABLE: [Option<&OpCode>; 256] = build_opcode_table();
+
+const fn build_opcode_table() -> [Option<&'static OpCode>; 256] {
+    let mut table = [None; 256];
+    let mut i = 0;
+    while i < CPU_OPCODES.len() {
+        let op = &CPU_OPCODES[i];
+        table[op.opcode as usize] = Some(op);
+        i += 1;
+    }
+    table
+}
+
 pub const CARRY: u8 = 0b0000_0001;
 pub const ZERO: u8 = 0b0000_0010;
+pub const INTERRUPT_DISABLE: u8 = 0b0000_0100;
+pub const DECIMAL: u8 = 0b0000_1000;
+pub const BREAK: u8 = 0b0001_0000;
+pub const UNUSED: u8 = 0b0010_0000;
 pub const OVERFLOW: u8 = 0b0100_0000;
 pub const SIGN: u8 = 0b1000_0000;
 
-impl CPU {
-    pub fn new() -> Self {
-        Self {
-            register_a: 0,
-            register_x: 0,
-            register_y: 0,
-            status: 0,
-            program_counter: 0,
+/// The status register as named flags. Converting to a byte always sets
+/// `UNUSED`, matching what PLP and RTI leave in the register.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StatusFlags {
+    pub carry: bool,
+    pub zero: bool,
+    pub interrupt_disable: bool,
+    pub decimal: bool,
+    pub break_command: bool,
+    pub overflow: bool,
+    pub negative: bool,
+}
 
-            memory: [0; 0xffff],
+impl From<u8> for StatusFlags {
+    fn from(status: u8) -> Self {
+        Self {
+            carry: status & CARRY != 0,
+            zero: status & ZERO != 0,
+            interrupt_disable: status & INTERRUPT_DISABLE != 0,
+            decimal: status & DECIMAL != 0,
+            break_command: status & BREAK != 0,
+            overflow: status & OVERFLOW != 0,
+            negative: status & SIGN != 0,
         }
     }
+}
 
-    pub fn load_and_run(&mut self, program: Vec<u8>) {
-        self.load(program);
-        self.reset();
-        self.run();
-    }
-
-    pub fn load(&mut self, program: Vec<u8>) {
-        self.memory[0x8000..(0x8000 + program.len())].copy_from_slice(&program[..]);
-        self.mem_write_u16(0xfffc, 0x8000);
+impl From<StatusFlags> for u8 {
+    fn from(flags: StatusFlags) -> Self {
+        [
+            (flags.carry, CARRY),
+            (flags.zero, ZERO),
+            (flags.interrupt_disable, INTERRUPT_DISABLE),
+            (flags.decimal, DECIMAL),
+            (flags.break_command, BREAK),
+            (flags.overflow, OVERFLOW),
+            (flags.negative, SIGN),
+        ]
+        .iter()
+        .filter(|(set, _)| *set)
+        .fold(UNUSED, |status, (_, flag)| status | flag)
     }
+}
 
-    pub fn reset(&mut self) {
-        self.register_a = 0;
-        self.register_x = 0;
-        self.status = 0;
+const STACK: u16 = 0x0100;
+const NMI_VECTOR: u16 = 0xfffa;
+const RESET_VECTOR: u16 = 0xfffc;
+const IRQ_VECTOR: u16 = 0xfffe;
+const STACK_RESET: u8 = 0xfd;
 
-        self.program_counter = self.mem_read_u16(0xfffc);
+impl CPU<FlatMemory> {
+    pub fn new() -> Self {
+        CPU::with_bus(FlatMemory::new())
     }
 
-    pub fn run(&mut self) {
-        use self::Mnemonic::*;
+    /// Starts building a CPU with preset registers, e.g.
+    /// `CPU::builder().a(0x10).status(CARRY).build()`.
+    pub fn builder() -> CpuBuilder {
+        CpuBuilder::default()
+    }
+}
 
-        loop {
-            let opcode = self.mem_read(self.program_counter);
-            self.program_counter += 1;
-
-            let mut done = false;
-            for op in CPU_OPCODES {
-                if op.opcode == opcode {
-                    match op.mnemonic {
-                        ADC => self.adc(op.addr_mode),
-                        BRK => return,
-                        INX => self.inx(),
-                        LDA => self.lda(op.addr_mode),
-                        SBC => self.sbc(op.addr_mode),
-                        STA => self.sta(op.addr_mode),
-                        TAX => self.tax(),
-                    }
+impl Default for CPU<FlatMemory> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
-                    self.program_counter += op.bytes as u16 - 1;
-                    done = true;
-                    break;
-                }
-            }
+/// Sets up registers for focused tests without a prologue of loads and
+/// transfers. Unset registers get the same values as `CPU::new`.
+#[derive(Debug, Clone)]
+pub struct CpuBuilder {
+    register_a: u8,
+    register_x: u8,
+    register_y: u8,
+    register_sp: u8,
+    status: u8,
+    program_counter: u16,
+}
 
-            if !done {
-                unimplemented!("opcode: {:x}", opcode);
-            }
+impl Default for CpuBuilder {
+    fn default() -> Self {
+        Self {
+            register_a: 0,
+            register_x: 0,
+            register_y: 0,
+            register_sp: STACK_RESET,
+            status: 0,
+            program_counter: 0,
         }
     }
+}
 
-    fn adc(&mut self, mode: AddressingMode) {
-        let a = self.register_a;
-        let m = self.mem_read(self.get_operand_address(mode));
-        let c = self.get_flag(CARRY) as u8;
+impl CpuBuilder {
+    pub fn a(mut self, value: u8) -> Self {
+        self.register_a = value;
+        self
+    }
 
-        let (a_m, overflow1) = (a as i8).overflowing_add(m as i8);
-        let (result, overflow2) = a_m.overflowing_add(c as i8);
-        let result_carry = (a as u16).wrapping_add(m as u16).wrapping_add(c as u16) >> 8;
-        let result_sign = result >> 7;
+    pub fn x(mut self, value: u8) -> Self {
+        self.register_x = value;
+        self
+    }
 
-        self.register_a = result as u8;
-        self.set_flag(CARRY, result_carry != 0);
-        self.set_flag(ZERO, result == 0);
-        self.set_flag(OVERFLOW, overflow1 | overflow2);
-        self.set_flag(SIGN, result_sign != 0);
+    pub fn y(mut self, value: u8) -> Self {
+        self.register_y = value;
+        self
     }
 
-    fn inx(&mut self) {
-        self.register_x = self.register_x.wrapping_add(1);
-        self.update_flags(self.register_x);
+    pub fn sp(mut self, value: u8) -> Self {
+        self.register_sp = value;
+        self
     }
 
-    fn lda(&mut self, mode: AddressingMode) {
-        let addr = self.get_operand_address(mode);
-        let value = self.mem_read(addr);
+    pub fn status(mut self, value: u8) -> Self {
+        self.status = value;
+        self
+    }
 
-        self.register_a = value;
-        self.update_flags(self.register_a);
+    pub fn program_counter(mut self, value: u16) -> Self {
+        self.program_counter = value;
+        self
     }
 
-    fn sbc(&mut self, mode: AddressingMode) {
-        let a = self.register_a;
-        let m = self.mem_read(self.get_operand_address(mode));
-        let c = !self.get_flag(CARRY) as u8;
+    pub fn build(self) -> CPU {
+        let mut cpu = CPU::new();
+        cpu.register_a = self.register_a;
+        cpu.register_x = self.register_x;
+        cpu.register_y = self.register_y;
+        cpu.register_sp = self.register_sp;
+        cpu.status = self.status;
+        cpu.program_counter = self.program_counter;
+        cpu
+    }
+}
 
-        let (a_m, overflow1) = (a as i8).overflowing_sub(m as i8);
-        let (result, overflow2) = a_m.overflowing_sub(c as i8);
-        let result_carry = (a as u16).wrapping_sub(m as u16).wrapping_sub(c as u16) >> 8;
-        let result_sign = result >> 7;
+impl<B: Bus> CPU<B> {
+    pub fn with_bus(bus: B) -> Self {
+        Self {
+            register_a: 0,
+            register_x: 0,
+            register_y: 0,
+            register_sp: STACK_RESET,
+            status: 0,
+            program_counter: 0,
+            decimal_enabled: true,
+            trap_self_loops: false,
+            stack_base: STACK,
+            bus,
 
-        self.register_a = result as u8;
-        self.set_flag(CARRY, result_carry != 0);
-        self.set_flag(ZERO, result == 0);
-        self.set_flag(OVERFLOW, overflow1 | overflow2);
-        self.set_flag(SIGN, result_sign != 0);
+            cycles: 0,
+            instructions: 0,
+            nmi_pending: false,
+            irq_pending: false,
+            breakpoints: BTreeSet::new(),
+            resumed_breakpoint: None,
+            watchpoints: BTreeMap::new(),
+            watchpoint_hit: None,
+            jumped: false,
+            hooks: Hooks::default(),
+            ticks: TickState::default(),
+            trace_capacity: 0,
+            trace_log: Vec::new(),
+        }
     }
 
-    fn sta(&mut self, mode: AddressingMode) {
-        let addr = self.get_operand_address(mode);
-        self.mem_write(addr, self.register_a);
+    /// Loads, resets and runs `program`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `program` does not fit in memory; use `load` to handle that
+    /// case.
+    pub fn load_and_run(&mut self, program: Vec<u8>) -> StopReason {
+        if let Err(err) = self.load(program) {
+            panic!("{}", err);
+        }
+        self.reset();
+        self.run()
     }
 
-    fn tax(&mut self) {
-        self.register_x = self.register_a;
-        self.update_flags(self.register_x);
+    pub fn load(&mut self, program: Vec<u8>) -> Result<(), LoadError> {
+        self.load_at(program, 0x8000)
     }
 
-    fn update_flags(&mut self, value: u8) {
-        self.set_flag(ZERO, value == 0);
-        self.set_flag(SIGN, value & 0b1000_0000 != 0);
+    /// Loads `program` at 0x8000, resets and runs for at most `max_cycles`.
+    /// Unlike `load_and_run` this never panics, so it can be fed arbitrary
+    /// input: bytes past the end of memory are dropped.
+    pub fn run_bytes(&mut self, program: &[u8], max_cycles: usize) -> StopReason {
+        let len = program.len().min(MEMORY_SIZE - 0x8000);
+        let loaded = self.load(program[..len].to_vec());
+        debug_assert!(loaded.is_ok());
+        self.reset();
+        self.run_for(max_cycles)
     }
 
-    fn set_flag(&mut self, flag: u8, status: bool) {
-        if status {
-            self.status |= flag;
-        } else {
-            self.status &= !flag;
+    /// Copies `program` to `addr` and points the reset vector at it.
+    pub fn load_at(&mut self, program: Vec<u8>, addr: u16) -> Result<(), LoadError> {
+        let start = addr as usize;
+        if program.len() > MEMORY_SIZE - start {
+            return Err(LoadError::TooLarge {
+                addr,
+                len: program.len(),
+            });
         }
-    }
 
-    fn get_flag(&mut self, flag: u8) -> bool {
-        self.status & flag != 0
+        for (i, byte) in program.into_iter().enumerate() {
+            self.mem_write(addr + i as u16, byte);
+        }
+        self.set_reset_vector(addr);
+        Ok(())
     }
 
-    fn mem_read(&self, addr: u16) -> u8 {
-        self.memory[addr as usize]
-    }
+    /// Maps the PRG-ROM of an iNES image at 0x8000, mirroring a single
+    /// 16KB bank into 0xc000.
+    pub fn load_rom(&mut self, data: &[u8]) -> Result<(), RomError> {
+        let rom = Rom::new(data)?;
 
-    fn mem_read_u16(&self, addr: u16) -> u16 {
-        let lo = self.mem_read(addr) as u16;
-        let hi = self.mem_read(addr + 1) as u16;
-        (hi << 8) | lo
+        for (i, &byte) in rom.prg_rom.iter().enumerate() {
+            self.mem_write(0x8000 + i as u16, byte);
+            if rom.prg_rom.len() == PRG_ROM_PAGE_SIZE {
+                self.mem_write(0xc000 + i as u16, byte);
+            }
+        }
+        Ok(())
     }
 
-    fn mem_write_u16(&mut self, addr: u16, data: u16) {
-        let lo = (data & 0xff) as u8;
-        let hi = (data >> 8) as u8;
-        self.mem_write(addr, lo);
-        self.mem_write(addr + 1, hi);
+    /// Loads a file with `load_rom` if it starts with an iNES header, or as a
+    /// raw program with `load` otherwise. Errors from either loader are
+    /// reported as `InvalidData`.
+    #[cfg(feature = "std")]
+    pub fn load_file<P: AsRef<Path>>(&mut self, path: P) -> io::Result<()> {
+        let data = std::fs::read(path)?;
+        match self.load_rom(&data) {
+            Err(RomError::InvalidMagic) => self
+                .load(data)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err)),
+            result => result.map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err)),
+        }
     }
 
-    fn mem_write(&mut self, addr: u16, data: u8) {
-        self.memory[addr as usize] = data;
+    /// Resets the registers and jumps through the reset vector, leaving
+    /// memory untouched like the real chip does. See `reset_full`.
+    pub fn reset(&mut self) {
+        self.register_a = 0;
+        self.register_x = 0;
+        self.register_y = 0;
+        self.register_sp = STACK_RESET;
+        self.status = INTERRUPT_DISABLE;
+        self.cycles = 0;
+        self.instructions = 0;
+        self.nmi_pending = false;
+        self.irq_pending = false;
+        self.ticks = TickState::default();
+        self.resumed_breakpoint = None;
+
+        self.program_counter = self.bus_read_u16(RESET_VECTOR);
     }
 
-    fn get_operand_address(&self, mode: AddressingMode) -> u16 {
-        use self::AddressingMode::*;
+    /// Zeroes every address with `Bus::poke`, then calls `reset`. Since the
+    /// reset vector is cleared too, the program counter ends up at 0x0000.
+    /// Useful for reusing one CPU across unrelated test programs.
+    pub fn reset_full(&mut self) {
+        for addr in 0..=0xffff {
+            self.bus.poke(addr, 0);
+        }
+        self.reset();
+    }
 
-        match mode {
-            Immediate => self.program_counter,
-            ZeroPage => self.mem_read(self.program_counter) as u16,
-            Absolute => self.mem_read_u16(self.program_counter),
+    /// Runs until a BRK is executed. BRK enters the IRQ handler, so calling
+    /// `run` again continues from there.
+    pub fn run(&mut self) -> StopReason {
+        self.run_with_callback(|_| {})
+    }
 
-            ZeroPageX => {
-                let base = self.mem_read(self.program_counter);
-                base.wrapping_add(self.register_x) as u16
-            }
-            ZeroPageY => {
-                let base = self.mem_read(self.program_counter);
-                base.wrapping_add(self.register_y) as u16
+    /// Like `run`, but calls `callback` with the CPU state before each instruction.
+    pub fn run_with_callback<F>(&mut self, mut callback: F) -> StopReason
+    where
+        F: FnMut(&CPU<B>),
+    {
+        loop {
+            callback(self);
+            if let Some(reason) = self.step().stop {
+                return reason;
             }
+        }
+    }
 
-            AbsoluteX => {
-                let base = self.mem_read_u16(self.program_counter);
-                base.wrapping_add(self.register_x as u16)
-            }
-            AbsoluteY => {
-                let base = self.mem_read_u16(self.program_counter);
-                base.wrapping_add(self.register_y as u16)
+    /// Like `run`, but gives up once `max_cycles` cycles have elapsed. The
+    /// instruction that crosses the budget still completes.
+    pub fn run_for(&mut self, max_cycles: usize) -> StopReason {
+        let limit = self.cycles.saturating_add(max_cycles);
+        while self.cycles < limit {
+            if let Some(reason) = self.step().stop {
+                return reason;
             }
+        }
+        StopReason::CyclesExhausted
+    }
 
-            IndirectX => {
-                let base = self.mem_read(self.program_counter) as u16;
-                let addr = base.wrapping_add(self.register_x as u16);
-                self.mem_read_u16(addr)
+    /// Like `run`, but sleeps as needed so execution averages `hz` cycles per
+    /// second, e.g. 1_789_773 for the NES. Sleeps happen once the CPU is at
+    /// least a millisecond ahead, so short stretches still run at full speed.
+    /// A rate of 0 means unthrottled, the same as `run`.
+    #[cfg(feature = "std")]
+    pub fn run_realtime(&mut self, hz: u32) -> StopReason {
+        if hz == 0 {
+            return self.run();
+        }
+
+        let start = Instant::now();
+        let start_cycles = self.cycles;
+        loop {
+            if let Some(reason) = self.step().stop {
+                return reason;
             }
-            IndirectY => {
-                let addr = self.mem_read(self.program_counter) as u16;
-                self.mem_read_u16(addr) + self.register_y as u16
+
+            let elapsed_cycles = (self.cycles - start_cycles) as u128;
+            let target = Duration::from_nanos((elapsed_cycles * 1_000_000_000 / hz as u128) as u64);
+            let elapsed = start.elapsed();
+            if target > elapsed + Duration::from_millis(1) {
+                std::thread::sleep(target - elapsed);
             }
+        }
+    }
+
+    /// Advances by a single clock cycle, for interleaving with devices that
+    /// run at a fixed ratio to the CPU. Instructions still execute as a
+    /// whole: their effects land on the tick that completes the base cycle
+    /// count, and page-cross or branch penalties follow as idle ticks.
+    /// Returns the stop reason of the instruction completed on this tick. A
+    /// tick that stops without executing anything, such as on a breakpoint,
+    /// is not counted as a cycle, and the instruction completes on the next.
+    pub fn tick(&mut self) -> Option<StopReason> {
+        if self.ticks.idle > 0 {
+            self.ticks.idle -= 1;
+            return None;
+        }
+
+        if self.ticks.elapsed == 0 {
+            self.ticks.base = self.upcoming_cycles();
+        }
+        self.ticks.elapsed += 1;
+        if self.ticks.elapsed < self.ticks.base {
+            return None;
+        }
 
-            NoneAddressing => panic!("{:?} is not supported", mode),
+        let result = self.step();
+        if result.cycles == 0 {
+            self.ticks.elapsed -= 1;
+            return result.stop;
         }
+        self.ticks.idle = result.cycles.saturating_sub(self.ticks.base);
+        self.ticks.elapsed = 0;
+        result.stop
     }
-}
 
-#[cfg(test)]
-mod test {
-    use super::*;
+    /// Requests a non-maskable interrupt, serviced before the next instruction.
+    pub fn trigger_nmi(&mut self) {
+        self.nmi_pending = true;
+    }
 
-    #[test]
-    fn test_0xa9_lda_immediate_load_data() {
-        let mut cpu = CPU::new();
-        cpu.load_and_run(vec![0xa9, 0x05, 0x00]);
-        assert_eq!(cpu.register_a, 0x05);
-        assert!(!cpu.get_flag(ZERO));
-        assert!(!cpu.get_flag(SIGN));
+    /// Requests a maskable interrupt. It is dropped if INTERRUPT_DISABLE is
+    /// set when the CPU gets to it.
+    pub fn trigger_irq(&mut self) {
+        self.irq_pending = true;
     }
 
-    #[test]
-    fn test_0xa9_lda_zero_flag() {
-        let mut cpu = CPU::new();
-        cpu.load_and_run(vec![0xa9, 0x00, 0x00]);
-        assert!(cpu.get_flag(ZERO));
+    /// Makes `run` and `step` stop before executing the instruction at
+    /// `addr`. Running again continues past it.
+    pub fn add_breakpoint(&mut self, addr: u16) {
+        self.breakpoints.insert(addr);
     }
 
-    #[test]
-    fn test_0xaa_tax_move_a_to_x() {
-        let mut cpu = CPU::new();
-        cpu.load_and_run(vec![0xa9, 0x10, 0xaa, 0x00]);
-        assert_eq!(cpu.register_x, 0x10);
+    pub fn remove_breakpoint(&mut self, addr: u16) {
+        self.breakpoints.remove(&addr);
     }
 
-    #[test]
-    fn test_5_ops_working_together() {
-        let mut cpu = CPU::new();
-        cpu.load_and_run(vec![0xa9, 0xc0, 0xaa, 0xe8, 0x00]);
-        assert_eq!(cpu.register_x, 0xc1);
+    /// Makes `run` and `step` stop after an instruction that reads or writes
+    /// `addr`, as selected by `on_read` and `on_write`. Reads of the pointer
+    /// behind an indirect operand count, but reads of the operand bytes
+    /// themselves do not.
+    pub fn add_watchpoint(&mut self, addr: u16, on_read: bool, on_write: bool) {
+        let watchpoint = Watchpoint {
+            read: on_read,
+            write: on_write,
+        };
+        self.watchpoints.insert(addr, watchpoint);
     }
 
-    #[test]
-    fn test_inx_overflow() {
-        let mut cpu = CPU::new();
-        cpu.load_and_run(vec![0xa9, 0xff, 0xaa, 0xe8, 0xe8, 0x00]);
-        assert_eq!(cpu.register_x, 0x1);
+    pub fn remove_watchpoint(&mut self, addr: u16) {
+        self.watchpoints.remove(&addr);
     }
 
-    #[test]
-    fn test_lda_from_memory() {
-        let mut cpu = CPU::new();
-        cpu.mem_write(0x10, 0x55);
-        cpu.load_and_run(vec![0xa5, 0x10, 0x00]);
-        assert_eq!(cpu.register_a, 0x55);
+    /// Calls `f` instead of reading the bus whenever an instruction reads
+    /// `addr`, replacing any earlier read hook for it.
+    pub fn on_read<F>(&mut self, addr: u16, f: F)
+    where
+        F: FnMut(u16) -> u8 + 'static,
+    {
+        self.hooks.reads.retain(|(hooked, _)| *hooked != addr);
+        self.hooks.reads.push((addr, Box::new(f)));
     }
 
-    #[test]
-    fn test_sta_move_a_to_memory() {
-        let mut cpu = CPU::new();
-        cpu.load_and_run(vec![0xa9, 0x10, 0x85, 0xff, 0x00]);
-        assert_eq!(cpu.mem_read(0x00ff), 0x10);
+    /// Calls `f` with the address and value after an instruction writes to
+    /// `addr`. `mem_write` does not trigger hooks.
+    pub fn on_write<F>(&mut self, addr: u16, f: F)
+    where
+        F: FnMut(u16, u8) + 'static,
+    {
+        self.hooks.writes.push((addr, Box::new(f)));
     }
 
-    #[test]
-    fn test_adc() {
-        let mut cpu = CPU::new();
-        cpu.load_and_run(vec![0xa9, 0x10, 0x69, 0x05, 0x00]);
+    pub fn step(&mut self) -> StepResult {
+        use self::Mnemonic::*;
+
+        // Drop hits from accesses outside `step` and from an opcode fetch
+        // that ended in another stop.
+        self.watchpoint_hit = None;
+
+        if let Some(vector) = self.pending_interrupt() {
+            self.interrupt(vector, UNUSED);
+            self.cycles += 7;
+            return StepResult {
+                stop: self.watchpoint_hit.take(),
+                cycles: 7,
+            };
+        }
+
+        let pc = self.program_counter;
+        if self.resumed_breakpoint.take() != Some(pc) && self.breakpoints.contains(&pc) {
+            self.resumed_breakpoint = Some(pc);
+            return StepResult {
+                stop: Some(StopReason::Breakpoint(pc)),
+                cycles: 0,
+            };
+        }
+
+        let opcode = self.bus_read(self.program_counter);
+        let op = match OPCODE_TABLE[opcode as usize] {
+            Some(op) => op,
+            None => {
+                self.watchpoint_hit = None;
+                return StepResult {
+                    stop: Some(StopReason::UnknownOpcode(opcode)),
+                    cycles: 0,
+                };
+            }
+        };
+
+        let end = self.program_counter as usize + op.bytes as usize;
+        if end > MEMORY_SIZE {
+            self.watchpoint_hit = None;
+            return StepResult {
+                stop: Some(StopReason::Halt),
+                cycles: 0,
+            };
+        }
+
+        if self.trace_capacity > 0 {
+            self.record_trace(op);
+        }
+
+        self.program_counter = self.program_counter.wrapping_add(1);
+        self.jumped = false;
+        let cycles = self.cycles;
+
+        match op.mnemonic {
+            ADC => self.adc(op.addr_mode),
+            AND => self.and(op.addr_mode),
+            ASL => self.asl(op.addr_mode),
+            BCC => self.bcc(),
+            BCS => self.bcs(),
+            BEQ => self.beq(),
+            BIT => self.bit(op.addr_mode),
+            BMI => self.bmi(),
+            BNE => self.bne(),
+            BPL => self.bpl(),
+            BRK => self.brk(),
+            BVC => self.bvc(),
+            BVS => self.bvs(),
+            CLC => self.clc(),
+            CLD => self.cld(),
+            CLI => self.cli(),
+            CLV => self.clv(),
+            CMP => self.cmp(op.addr_mode),
+            CPX => self.cpx(op.addr_mode),
+            CPY => self.cpy(op.addr_mode),
+            DCP => self.dcp(op.addr_mode),
+            DEC => self.dec(op.addr_mode),
+            DEX => self.dex(),
+            DEY => self.dey(),
+            EOR => self.eor(op.addr_mode),
+            INC => self.inc(op.addr_mode),
+            INX => self.inx(),
+            INY => self.iny(),
+            ISC => self.isc(op.addr_mode),
+            JMP => self.jmp(op.addr_mode),
+            LAX => self.lax(op.addr_mode),
+            LDA => self.lda(op.addr_mode),
+            LDX => self.ldx(op.addr_mode),
+            LDY => self.ldy(op.addr_mode),
+            LSR => self.lsr(op.addr_mode),
+            NOP => self.nop(op.addr_mode),
+            ORA => self.ora(op.addr_mode),
+            PHA => self.pha(),
+            PHP => self.php(),
+            PLA => self.pla(),
+            PLP => self.plp(),
+            RLA => self.rla(op.addr_mode),
+            ROL => self.rol(op.addr_mode),
+            ROR => self.ror(op.addr_mode),
+            RRA => self.rra(op.addr_mode),
+            RTI => self.rti(),
+            SAX => self.sax(op.addr_mode),
+            SBC => self.sbc(op.addr_mode),
+            SEC => self.sec(),
+            SED => self.sed(),
+            SEI => self.sei(),
+            SLO => self.slo(op.addr_mode),
+            SRE => self.sre(op.addr_mode),
+            STA => self.sta(op.addr_mode),
+            STX => self.stx(op.addr_mode),
+            STY => self.sty(op.addr_mode),
+            TAX => self.tax(),
+            TAY => self.tay(),
+            TSX => self.tsx(),
+            TXA => self.txa(),
+            TXS => self.txs(),
+            TYA => self.tya(),
+        }
+
+        if !self.jumped {
+            self.program_counter = self.program_counter.wrapping_add(op.bytes as u16 - 1);
+        }
+        let ran_off = !self.jumped && end == MEMORY_SIZE;
+        self.cycles += op.cycles as usize;
+        if op.mnemonic != BRK {
+            self.instructions += 1;
+        }
+        let watchpoint_hit = self.watchpoint_hit.take();
+
+        let trapped = self.trap_self_loops && self.program_counter == pc;
+
+        StepResult {
+            stop: match op.mnemonic {
+                _ if watchpoint_hit.is_some() => watchpoint_hit,
+                BRK => Some(StopReason::Break),
+                _ if ran_off => Some(StopReason::Halt),
+                _ if trapped => Some(StopReason::Trap(pc)),
+                _ => None,
+            },
+            cycles: (self.cycles - cycles) as u8,
+        }
+    }
+
+    fn record_trace(&mut self, op: &OpCode) {
+        // Keep up to twice the capacity so old entries are dropped in batches
+        // rather than shifting the log on every instruction.
+        if self.trace_log.len() == 2 * self.trace_capacity {
+            self.trace_log.drain(..self.trace_capacity);
+        }
+        let pc = self.program_counter;
+        self.trace_log.push(TraceEntry {
+            addr: pc,
+            opcode: op.opcode,
+            disassembly: self.format_instruction(op, pc),
+            register_a: self.register_a,
+            register_x: self.register_x,
+            register_y: self.register_y,
+            register_sp: self.register_sp,
+            status: self.status,
+        });
+    }
+
+    /// Predicts the base cycle count of what the next `step` will do, without
+    /// side effects.
+    fn upcoming_cycles(&self) -> u8 {
+        if self.nmi_pending || (self.irq_pending && !self.get_flag(INTERRUPT_DISABLE)) {
+            return 7;
+        }
+        match OPCODE_TABLE[self.mem_read(self.program_counter) as usize] {
+            Some(op) => op.cycles,
+            None => 1,
+        }
+    }
+
+    fn pending_interrupt(&mut self) -> Option<u16> {
+        if self.nmi_pending {
+            self.nmi_pending = false;
+            return Some(NMI_VECTOR);
+        }
+
+        if self.irq_pending {
+            self.irq_pending = false;
+            if !self.get_flag(INTERRUPT_DISABLE) {
+                return Some(IRQ_VECTOR);
+            }
+        }
+
+        None
+    }
+
+    /// Serializes the whole machine state, memory included.
+    #[cfg(feature = "serde")]
+    pub fn save_state(&self) -> Vec<u8>
+    where
+        B: serde::Serialize,
+    {
+        bincode::serialize(self).expect("CPU state is always serializable")
+    }
+
+    /// Restores a state produced by `save_state`. Registered hooks and the
+    /// trace set up by `enable_trace`, log included, are kept.
+    #[cfg(feature = "serde")]
+    pub fn load_state(&mut self, bytes: &[u8]) -> Result<(), bincode::Error>
+    where
+        B: serde::de::DeserializeOwned,
+    {
+        let mut state: Self = bincode::deserialize(bytes)?;
+        state.hooks = core::mem::take(&mut self.hooks);
+        state.trace_capacity = self.trace_capacity;
+        state.trace_log = core::mem::take(&mut self.trace_log);
+        *self = state;
+        Ok(())
+    }
+
+    pub fn nmi_vector(&self) -> u16 {
+        self.mem_read_u16(NMI_VECTOR)
+    }
+
+    pub fn set_nmi_vector(&mut self, addr: u16) {
+        self.mem_write_u16(NMI_VECTOR, addr);
+    }
+
+    pub fn reset_vector(&self) -> u16 {
+        self.mem_read_u16(RESET_VECTOR)
+    }
+
+    pub fn set_reset_vector(&mut self, addr: u16) {
+        self.mem_write_u16(RESET_VECTOR, addr);
+    }
+
+    /// The IRQ vector, which BRK also jumps through.
+    pub fn irq_vector(&self) -> u16 {
+        self.mem_read_u16(IRQ_VECTOR)
+    }
+
+    pub fn set_irq_vector(&mut self, addr: u16) {
+        self.mem_write_u16(IRQ_VECTOR, addr);
+    }
+
+    pub fn total_cycles(&self) -> usize {
+        self.cycles
+    }
+
+    /// Returns how many instructions have executed since the last reset.
+    /// BRK, which stops `run`, and interrupt entries are not counted, so after
+    /// `run` this is the number of instructions before the BRK.
+    pub fn instruction_count(&self) -> usize {
+        self.instructions
+    }
+
+    pub fn register_a_signed(&self) -> i8 {
+        self.register_a as i8
+    }
+
+    pub fn register_x_signed(&self) -> i8 {
+        self.register_x as i8
+    }
+
+    pub fn register_y_signed(&self) -> i8 {
+        self.register_y as i8
+    }
+
+    pub fn flags(&self) -> StatusFlags {
+        StatusFlags::from(self.status)
+    }
+
+    pub fn set_flags(&mut self, flags: StatusFlags) {
+        self.status = flags.into();
+    }
+
+    /// Starts recording the last `capacity` executed instructions, discarding
+    /// anything recorded so far. A capacity of 0 turns recording off.
+    pub fn enable_trace(&mut self, capacity: usize) {
+        self.trace_capacity = capacity;
+        self.trace_log = Vec::with_capacity(2 * capacity);
+    }
+
+    /// Returns the recorded instructions, oldest first.
+    pub fn trace_log(&self) -> &[TraceEntry] {
+        let start = self.trace_log.len().saturating_sub(self.trace_capacity);
+        &self.trace_log[start..]
+    }
+
+    /// Renders the status register as "NV-BDIZC", with set flags in
+    /// uppercase and clear flags in lowercase.
+    pub fn status_string(&self) -> String {
+        "NV-BDIZC"
+            .chars()
+            .enumerate()
+            .map(|(i, c)| {
+                if c == '-' || self.status & (SIGN >> i) != 0 {
+                    c
+                } else {
+                    c.to_ascii_lowercase()
+                }
+            })
+            .collect()
+    }
+
+    /// Copies `len` bytes starting at `start`, wrapping at the end of the
+    /// address space.
+    pub fn dump(&self, start: u16, len: usize) -> Vec<u8> {
+        (0..len)
+            .map(|i| self.mem_read(start.wrapping_add(i as u16)))
+            .collect()
+    }
+
+    /// Whether memory starting at `addr` matches `expected`, wrapping like
+    /// `dump`.
+    pub fn mem_eq(&self, addr: u16, expected: &[u8]) -> bool {
+        self.mem_diff(addr, expected).is_empty()
+    }
+
+    /// Returns `(address, actual, expected)` for every byte starting at
+    /// `addr` that differs from `expected`.
+    pub fn mem_diff(&self, addr: u16, expected: &[u8]) -> Vec<(u16, u8, u8)> {
+        expected
+            .iter()
+            .enumerate()
+            .map(|(i, &byte)| {
+                let addr = addr.wrapping_add(i as u16);
+                (addr, self.mem_read(addr), byte)
+            })
+            .filter(|&(_, actual, expected)| actual != expected)
+            .collect()
+    }
+
+    /// Formats `len` bytes starting at `start` like `hexdump -C`, 16 bytes per
+    /// line.
+    pub fn hexdump(&self, start: u16, len: usize) -> String {
+        let mut out = String::new();
+        for (row, chunk) in self.dump(start, len).chunks(16).enumerate() {
+            let addr = start.wrapping_add((row as u16).wrapping_mul(16));
+            out.push_str(&format!("{:04x} ", addr));
+            for i in 0..16 {
+                if i == 8 {
+                    out.push(' ');
+                }
+                match chunk.get(i) {
+                    Some(byte) => out.push_str(&format!(" {:02x}", byte)),
+                    None => out.push_str("   "),
+                }
+            }
+            let ascii: String = chunk
+                .iter()
+                .map(|&byte| {
+                    if byte.is_ascii_graphic() || byte == b' ' {
+                        byte as char
+                    } else {
+                        '.'
+                    }
+                })
+                .collect();
+            out.push_str(&format!("  |{}|\n", ascii));
+        }
+        out
+    }
+
+    /// Decodes the instruction at the program counter without running it, or
+    /// returns None if the byte there is not a known opcode.
+    pub fn peek_instruction(&self) -> Option<DecodedInstruction> {
+        let pc = self.program_counter;
+        let op = OPCODE_TABLE[self.mem_read(pc) as usize]?;
+        Some(DecodedInstruction {
+            mnemonic: op.mnemonic,
+            addr_mode: op.addr_mode,
+            operand: (1..op.bytes as u16)
+                .map(|i| self.mem_read(pc.wrapping_add(i)))
+                .collect(),
+            text: self.format_instruction(op, pc),
+        })
+    }
+
+    /// Disassembles the instructions in `start..end`. Bytes that are not a
+    /// known opcode, or whose operands would run past `end`, are rendered as
+    /// ".byte $xx".
+    pub fn disassemble(&self, start: u16, end: u16) -> Vec<(u16, String)> {
+        let mut lines = Vec::new();
+        let mut addr = start as usize;
+        while addr < end as usize {
+            let opcode = self.mem_read(addr as u16);
+            match OPCODE_TABLE[opcode as usize] {
+                Some(op) if addr + op.bytes as usize <= end as usize => {
+                    lines.push((addr as u16, self.format_instruction(op, addr as u16)));
+                    addr += op.bytes as usize;
+                }
+                _ => {
+                    lines.push((addr as u16, format!(".byte ${:02X}", opcode)));
+                    addr += 1;
+                }
+            }
+        }
+        lines
+    }
+
+    fn format_instruction(&self, op: &OpCode, addr: u16) -> String {
+        use self::AddressingMode::*;
+
+        let arg = self.mem_read(addr.wrapping_add(1));
+        let arg_u16 = self.mem_read_u16(addr.wrapping_add(1));
+        let operand = match op.addr_mode {
+            Immediate => format!("#${:02X}", arg),
+            ZeroPage => format!("${:02X}", arg),
+            ZeroPageX => format!("${:02X},X", arg),
+            ZeroPageY => format!("${:02X},Y", arg),
+            Absolute => format!("${:04X}", arg_u16),
+            AbsoluteX => format!("${:04X},X", arg_u16),
+            AbsoluteY => format!("${:04X},Y", arg_u16),
+            Indirect => format!("(${:04X})", arg_u16),
+            IndirectX => format!("(${:02X},X)", arg),
+            IndirectY => format!("(${:02X}),Y", arg),
+            Relative => {
+                let target = addr.wrapping_add(2).wrapping_add(arg as i8 as u16);
+                format!("${:04X}", target)
+            }
+            Accumulator => "A".to_string(),
+            NoneAddressing => return op.mnemonic.to_string(),
+        };
+        format!("{} {}", op.mnemonic, operand)
+    }
+
+    /// Formats the instruction at the program counter like a line of
+    /// nestest.log, without the PPU column.
+    pub fn trace(&self) -> String {
+        let pc = self.program_counter;
+        let opcode = self.mem_read(pc);
+        let (hex, asm) = match OPCODE_TABLE[opcode as usize] {
+            Some(op) => {
+                let hex: Vec<String> = (0..op.bytes as u16)
+                    .map(|i| format!("{:02X}", self.mem_read(pc.wrapping_add(i))))
+                    .collect();
+                let marker = if op.is_unofficial() { '*' } else { ' ' };
+                let asm = format!(
+                    "{}{}{}",
+                    marker,
+                    self.format_instruction(op, pc),
+                    self.trace_operand_value(op, pc)
+                );
+                (hex.join(" "), asm)
+            }
+            None => (format!("{:02X}", opcode), format!(" .byte ${:02X}", opcode)),
+        };
+
+        format!(
+            "{:04X}  {:8} {:33}A:{:02X} X:{:02X} Y:{:02X} P:{:02X} SP:{:02X} CYC:{}",
+            pc,
+            hex,
+            asm,
+            self.register_a,
+            self.register_x,
+            self.register_y,
+            self.status,
+            self.register_sp,
+            self.cycles
+        )
+    }
+
+    /// The effective address and memory contents nestest appends to an
+    /// operand, e.g. " @ 33 = 00" for "STA $33,X".
+    fn trace_operand_value(&self, op: &OpCode, pc: u16) -> String {
+        use self::AddressingMode::*;
+
+        let operand = pc.wrapping_add(1);
+        match op.addr_mode {
+            Immediate | Relative | Accumulator | NoneAddressing => String::new(),
+            Absolute if matches!(op.mnemonic, Mnemonic::JMP) => String::new(),
+            ZeroPage | Absolute => {
+                let (addr, _) = self.get_absolute_address(op.addr_mode, operand);
+                format!(" = {:02X}", self.mem_read(addr))
+            }
+            ZeroPageX | ZeroPageY => {
+                let (addr, _) = self.get_absolute_address(op.addr_mode, operand);
+                format!(" @ {:02X} = {:02X}", addr, self.mem_read(addr))
+            }
+            AbsoluteX | AbsoluteY => {
+                let (addr, _) = self.get_absolute_address(op.addr_mode, operand);
+                format!(" @ {:04X} = {:02X}", addr, self.mem_read(addr))
+            }
+            Indirect => {
+                let (addr, _) = self.get_absolute_address(op.addr_mode, operand);
+                format!(" = {:04X}", addr)
+            }
+            IndirectX => {
+                let ptr = self.mem_read(operand).wrapping_add(self.register_x);
+                let (addr, _) = self.get_absolute_address(op.addr_mode, operand);
+                format!(
+                    " @ {:02X} = {:04X} = {:02X}",
+                    ptr,
+                    addr,
+                    self.mem_read(addr)
+                )
+            }
+            IndirectY => {
+                let (addr, _) = self.get_absolute_address(op.addr_mode, operand);
+                let base = addr.wrapping_sub(self.register_y as u16);
+                format!(
+                    " = {:04X} @ {:04X} = {:02X}",
+                    base,
+                    addr,
+                    self.mem_read(addr)
+                )
+            }
+        }
+    }
+
+    fn adc(&mut self, mode: AddressingMode) {
+        let value = self.operand_value(mode);
+        self.add(value);
+    }
+
+    fn and(&mut self, mode: AddressingMode) {
+        let value = self.operand_value(mode);
+
+        self.register_a &= value;
+        self.update_flags(self.register_a);
+    }
+
+    fn asl(&mut self, mode: AddressingMode) {
+        let result = self.modify(mode, Self::shift_left);
+        self.update_flags(result);
+    }
+
+    fn bcc(&mut self) {
+        self.branch(!self.get_flag(CARRY));
+    }
+
+    fn bcs(&mut self) {
+        self.branch(self.get_flag(CARRY));
+    }
+
+    fn beq(&mut self) {
+        self.branch(self.get_flag(ZERO));
+    }
+
+    fn bit(&mut self, mode: AddressingMode) {
+        let value = self.operand_value(mode);
+
+        self.set_flag(ZERO, self.register_a & value == 0);
+        self.set_flag(OVERFLOW, value & 0b0100_0000 != 0);
+        self.set_flag(SIGN, value & 0b1000_0000 != 0);
+    }
+
+    fn bmi(&mut self) {
+        self.branch(self.get_flag(SIGN));
+    }
+
+    fn bne(&mut self) {
+        self.branch(!self.get_flag(ZERO));
+    }
+
+    fn bpl(&mut self) {
+        self.branch(!self.get_flag(SIGN));
+    }
+
+    fn brk(&mut self) {
+        // BRK is followed by a padding byte that the return address skips over.
+        self.program_counter = self.program_counter.wrapping_add(1);
+        self.interrupt(IRQ_VECTOR, BREAK | UNUSED);
+    }
+
+    fn bvc(&mut self) {
+        self.branch(!self.get_flag(OVERFLOW));
+    }
+
+    fn bvs(&mut self) {
+        self.branch(self.get_flag(OVERFLOW));
+    }
+
+    fn clc(&mut self) {
+        self.set_flag(CARRY, false);
+    }
+
+    fn cld(&mut self) {
+        self.set_flag(DECIMAL, false);
+    }
+
+    fn cli(&mut self) {
+        self.set_flag(INTERRUPT_DISABLE, false);
+    }
+
+    fn clv(&mut self) {
+        self.set_flag(OVERFLOW, false);
+    }
+
+    fn cmp(&mut self, mode: AddressingMode) {
+        let value = self.operand_value(mode);
+        self.compare(self.register_a, value);
+    }
+
+    fn cpx(&mut self, mode: AddressingMode) {
+        let value = self.operand_value(mode);
+        self.compare(self.register_x, value);
+    }
+
+    fn cpy(&mut self, mode: AddressingMode) {
+        let value = self.operand_value(mode);
+        self.compare(self.register_y, value);
+    }
+
+    fn dcp(&mut self, mode: AddressingMode) {
+        let value = self.modify(mode, |_, value| value.wrapping_sub(1));
+        self.compare(self.register_a, value);
+    }
+
+    fn dec(&mut self, mode: AddressingMode) {
+        let value = self.modify(mode, |_, value| value.wrapping_sub(1));
+        self.update_flags(value);
+    }
+
+    fn dex(&mut self) {
+        self.register_x = self.register_x.wrapping_sub(1);
+        self.update_flags(self.register_x);
+    }
+
+    fn dey(&mut self) {
+        self.register_y = self.register_y.wrapping_sub(1);
+        self.update_flags(self.register_y);
+    }
+
+    fn eor(&mut self, mode: AddressingMode) {
+        let value = self.operand_value(mode);
+
+        self.register_a ^= value;
+        self.update_flags(self.register_a);
+    }
+
+    fn inc(&mut self, mode: AddressingMode) {
+        let value = self.modify(mode, |_, value| value.wrapping_add(1));
+        self.update_flags(value);
+    }
+
+    fn inx(&mut self) {
+        self.register_x = self.register_x.wrapping_add(1);
+        self.update_flags(self.register_x);
+    }
+
+    fn iny(&mut self) {
+        self.register_y = self.register_y.wrapping_add(1);
+        self.update_flags(self.register_y);
+    }
+
+    fn isc(&mut self, mode: AddressingMode) {
+        let value = self.modify(mode, |_, value| value.wrapping_add(1));
+        self.subtract(value);
+    }
+
+    fn jmp(&mut self, mode: AddressingMode) {
+        let addr = self.get_operand_address(mode);
+        self.jump(addr);
+    }
+
+    fn lax(&mut self, mode: AddressingMode) {
+        let value = self.operand_value(mode);
+
+        self.register_a = value;
+        self.register_x = value;
+        self.update_flags(value);
+    }
+
+    fn lda(&mut self, mode: AddressingMode) {
+        let value = self.operand_value(mode);
+
+        self.register_a = value;
+        self.update_flags(self.register_a);
+    }
+
+    fn ldx(&mut self, mode: AddressingMode) {
+        let value = self.operand_value(mode);
+
+        self.register_x = value;
+        self.update_flags(self.register_x);
+    }
+
+    fn ldy(&mut self, mode: AddressingMode) {
+        let value = self.operand_value(mode);
+
+        self.register_y = value;
+        self.update_flags(self.register_y);
+    }
+
+    fn lsr(&mut self, mode: AddressingMode) {
+        let result = self.modify(mode, Self::shift_right);
+        self.update_flags(result);
+    }
+
+    fn nop(&mut self, mode: AddressingMode) {
+        // The unofficial variants still fetch their operand.
+        if !matches!(mode, AddressingMode::NoneAddressing) {
+            self.operand_value(mode);
+        }
+    }
+
+    fn ora(&mut self, mode: AddressingMode) {
+        let value = self.operand_value(mode);
+
+        self.register_a |= value;
+        self.update_flags(self.register_a);
+    }
+
+    fn pha(&mut self) {
+        self.stack_push(self.register_a);
+    }
+
+    fn php(&mut self) {
+        self.stack_push(self.status | BREAK | UNUSED);
+    }
+
+    fn pla(&mut self) {
+        self.register_a = self.stack_pull();
+        self.update_flags(self.register_a);
+    }
+
+    fn plp(&mut self) {
+        self.status = self.stack_pull() & !BREAK | UNUSED;
+    }
+
+    fn rla(&mut self, mode: AddressingMode) {
+        let result = self.modify(mode, Self::rotate_left);
+        self.register_a &= result;
+        self.update_flags(self.register_a);
+    }
+
+    fn rol(&mut self, mode: AddressingMode) {
+        let result = self.modify(mode, Self::rotate_left);
+        self.update_flags(result);
+    }
+
+    fn ror(&mut self, mode: AddressingMode) {
+        let result = self.modify(mode, Self::rotate_right);
+        self.update_flags(result);
+    }
+
+    fn rra(&mut self, mode: AddressingMode) {
+        let result = self.modify(mode, Self::rotate_right);
+        self.add(result);
+    }
+
+    fn rti(&mut self) {
+        self.plp();
+        let addr = self.stack_pull_u16();
+        self.jump(addr);
+    }
+
+    fn sax(&mut self, mode: AddressingMode) {
+        self.store(mode, self.register_a & self.register_x);
+    }
+
+    fn sbc(&mut self, mode: AddressingMode) {
+        let value = self.operand_value(mode);
+        self.subtract(value);
+    }
+
+    fn sec(&mut self) {
+        self.set_flag(CARRY, true);
+    }
+
+    fn sed(&mut self) {
+        self.set_flag(DECIMAL, true);
+    }
+
+    fn sei(&mut self) {
+        self.set_flag(INTERRUPT_DISABLE, true);
+    }
+
+    fn slo(&mut self, mode: AddressingMode) {
+        let result = self.modify(mode, Self::shift_left);
+        self.register_a |= result;
+        self.update_flags(self.register_a);
+    }
+
+    fn sre(&mut self, mode: AddressingMode) {
+        let result = self.modify(mode, Self::shift_right);
+        self.register_a ^= result;
+        self.update_flags(self.register_a);
+    }
+
+    fn sta(&mut self, mode: AddressingMode) {
+        self.store(mode, self.register_a);
+    }
+
+    fn stx(&mut self, mode: AddressingMode) {
+        self.store(mode, self.register_x);
+    }
+
+    fn sty(&mut self, mode: AddressingMode) {
+        self.store(mode, self.register_y);
+    }
+
+    fn tax(&mut self) {
+        self.register_x = self.register_a;
+        self.update_flags(self.register_x);
+    }
+
+    fn tay(&mut self) {
+        self.register_y = self.register_a;
+        self.update_flags(self.register_y);
+    }
+
+    fn tsx(&mut self) {
+        self.register_x = self.register_sp;
+        self.update_flags(self.register_x);
+    }
+
+    fn txa(&mut self) {
+        self.register_a = self.register_x;
+        self.update_flags(self.register_a);
+    }
+
+    fn txs(&mut self) {
+        self.register_sp = self.register_x;
+    }
+
+    fn tya(&mut self) {
+        self.register_a = self.register_y;
+        self.update_flags(self.register_a);
+    }
+
+    fn branch(&mut self, condition: bool) {
+        if condition {
+            let next = self.program_counter.wrapping_add(1);
+            let target = self.get_operand_address(AddressingMode::Relative);
+
+            self.cycles += 1;
+            if page_crossed(next, target) {
+                self.cycles += 1;
+            }
+            self.jump(target);
+        }
+    }
+
+    fn interrupt(&mut self, vector: u16, flags: u8) {
+        self.stack_push_u16(self.program_counter);
+        self.stack_push(self.status & !BREAK | flags);
+        self.set_flag(INTERRUPT_DISABLE, true);
+        let addr = self.bus_read_u16(vector);
+        self.jump(addr);
+    }
+
+    /// Transfers control to `addr`. Instructions that change the program
+    /// counter must go through here rather than assigning it, otherwise
+    /// `step` also skips the operand bytes.
+    fn jump(&mut self, addr: u16) {
+        self.program_counter = addr;
+        self.jumped = true;
+    }
+
+    /// Adds `m` and the carry to A, as ADC does.
+    fn add(&mut self, m: u8) {
+        let a = self.register_a;
+        let c = self.get_flag(CARRY) as u8;
+
+        self.register_a = self.set_add_flags(a, m, c);
+
+        if self.decimal_mode() {
+            let (result, carry) = bcd_add(a, m, c);
+            self.register_a = result;
+            self.set_flag(CARRY, carry);
+            self.update_flags(result);
+        }
+    }
+
+    /// Computes `a + operand + carry_in` and sets CARRY, ZERO, OVERFLOW and
+    /// SIGN from it, returning the result. Overflow means both inputs had the
+    /// same sign and the result's sign differs.
+    fn set_add_flags(&mut self, a: u8, operand: u8, carry_in: u8) -> u8 {
+        let sum = a as u16 + operand as u16 + carry_in as u16;
+        let result = sum as u8;
+
+        self.set_flag(CARRY, sum > 0xff);
+        self.set_flag(OVERFLOW, (a ^ result) & (operand ^ result) & 0x80 != 0);
+        self.update_flags(result);
+        result
+    }
+
+    fn compare(&mut self, register: u8, value: u8) {
+        self.set_flag(CARRY, register >= value);
+        self.update_flags(register.wrapping_sub(value));
+    }
+
+    fn shift_left(&mut self, value: u8) -> u8 {
+        self.set_flag(CARRY, value >> 7 != 0);
+        value << 1
+    }
+
+    fn shift_right(&mut self, value: u8) -> u8 {
+        self.set_flag(CARRY, value & 1 != 0);
+        value >> 1
+    }
+
+    fn rotate_left(&mut self, value: u8) -> u8 {
+        let carry = self.get_flag(CARRY) as u8;
+        self.set_flag(CARRY, value >> 7 != 0);
+        value << 1 | carry
+    }
+
+    fn rotate_right(&mut self, value: u8) -> u8 {
+        let carry = self.get_flag(CARRY) as u8;
+        self.set_flag(CARRY, value & 1 != 0);
+        value >> 1 | carry << 7
+    }
+
+    /// Subtracts `m` and the borrow from A, as SBC does.
+    fn subtract(&mut self, m: u8) {
+        let a = self.register_a;
+        let borrow = !self.get_flag(CARRY) as u8;
+
+        // A - M - borrow is A + !M + carry in two's complement.
+        self.register_a = self.set_add_flags(a, !m, 1 - borrow);
+
+        if self.decimal_mode() {
+            let result = bcd_sub(a, m, borrow);
+            self.register_a = result;
+            self.update_flags(result);
+        }
+    }
+
+    fn update_flags(&mut self, value: u8) {
+        self.set_flag(ZERO, value == 0);
+        self.set_flag(SIGN, value & 0b1000_0000 != 0);
+    }
+
+    fn decimal_mode(&self) -> bool {
+        self.decimal_enabled && self.get_flag(DECIMAL)
+    }
+
+    fn set_flag(&mut self, flag: u8, status: bool) {
+        if status {
+            self.status |= flag;
+        } else {
+            self.status &= !flag;
+        }
+    }
+
+    fn get_flag(&self, flag: u8) -> bool {
+        self.status & flag != 0
+    }
+
+    fn stack_push(&mut self, data: u8) {
+        let addr = self.stack_base.wrapping_add(self.register_sp as u16);
+        self.bus_write(addr, data);
+        self.register_sp = self.register_sp.wrapping_sub(1);
+    }
+
+    fn stack_push_u16(&mut self, data: u16) {
+        self.stack_push((data >> 8) as u8);
+        self.stack_push((data & 0xff) as u8);
+    }
+
+    fn stack_pull(&mut self) -> u8 {
+        self.register_sp = self.register_sp.wrapping_add(1);
+        let addr = self.stack_base.wrapping_add(self.register_sp as u16);
+        self.bus_read(addr)
+    }
+
+    fn stack_pull_u16(&mut self) -> u16 {
+        let lo = self.stack_pull() as u16;
+        let hi = self.stack_pull() as u16;
+        (hi << 8) | lo
+    }
+
+    /// Peeks a byte on the bus, bypassing any memory-mapped side effects.
+    /// Watchpoints and hooks are not triggered.
+    pub fn mem_read(&self, addr: u16) -> u8 {
+        self.bus.peek(addr)
+    }
+
+    /// Reads a little-endian word; see `mem_read`.
+    pub fn mem_read_u16(&self, addr: u16) -> u16 {
+        let lo = self.mem_read(addr) as u16;
+        let hi = self.mem_read(addr.wrapping_add(1)) as u16;
+        (hi << 8) | lo
+    }
+
+    /// Writes a little-endian word; see `mem_write`.
+    pub fn mem_write_u16(&mut self, addr: u16, data: u16) {
+        let lo = (data & 0xff) as u8;
+        let hi = (data >> 8) as u8;
+        self.mem_write(addr, lo);
+        self.mem_write(addr.wrapping_add(1), hi);
+    }
+
+    /// Pokes a byte on the bus, bypassing any memory-mapped side effects.
+    /// Watchpoints and hooks are not triggered.
+    pub fn mem_write(&mut self, addr: u16, data: u8) {
+        self.bus.poke(addr, data);
+    }
+
+    fn bus_read(&mut self, addr: u16) -> u8 {
+        self.check_watchpoint(addr, false);
+        for (hooked, hook) in self.hooks.reads.iter_mut() {
+            if *hooked == addr {
+                return hook(addr);
+            }
+        }
+        self.bus.read(addr)
+    }
+
+    fn bus_read_u16(&mut self, addr: u16) -> u16 {
+        let lo = self.bus_read(addr) as u16;
+        let hi = self.bus_read(addr.wrapping_add(1)) as u16;
+        (hi << 8) | lo
+    }
+
+    fn bus_write(&mut self, addr: u16, data: u8) {
+        self.check_watchpoint(addr, true);
+        self.bus.write(addr, data);
+        for (hooked, hook) in self.hooks.writes.iter_mut() {
+            if *hooked == addr {
+                hook(addr, data);
+            }
+        }
+    }
+
+    fn check_watchpoint(&mut self, addr: u16, write: bool) {
+        if self.watchpoint_hit.is_some() {
+            return;
+        }
+        if let Some(watchpoint) = self.watchpoints.get(&addr) {
+            if (write && watchpoint.write) || (!write && watchpoint.read) {
+                self.watchpoint_hit = Some(StopReason::Watchpoint { addr, write });
+            }
+        }
+    }
+
+    /// Performs the bus accesses of a read-modify-write instruction: the read,
+    /// a write of the unmodified value while the chip computes the result,
+    /// then the write of the result, which is returned. The `Accumulator` forms
+    /// modify A without touching the bus.
+    fn modify(&mut self, mode: AddressingMode, f: fn(&mut Self, u8) -> u8) -> u8 {
+        if mode == AddressingMode::Accumulator {
+            self.register_a = f(self, self.register_a);
+            return self.register_a;
+        }
+
+        let addr = self.get_operand_address(mode);
+        let value = self.bus_read(addr);
+        self.bus_write(addr, value);
+        let result = f(self, value);
+        self.bus_write(addr, result);
+        result
+    }
+
+    /// Writes `data` to the operand address of a store instruction.
+    fn store(&mut self, mode: AddressingMode, data: u8) {
+        let addr = self.get_operand_address(mode);
+        self.bus_write(addr, data);
+    }
+
+    /// Reads the operand of a read instruction, charging the extra cycle
+    /// taken when indexing crosses a page.
+    fn operand_value(&mut self, mode: AddressingMode) -> u8 {
+        let (addr, page_crossed) = self.get_operand_address_with_page_cross(mode);
+        if page_crossed {
+            self.cycles += 1;
+        }
+        self.bus_read(addr)
+    }
+
+    fn get_operand_address(&mut self, mode: AddressingMode) -> u16 {
+        self.get_operand_address_with_page_cross(mode).0
+    }
+
+    /// Also reports whether indexing moved the effective address onto another
+    /// page, which costs an extra cycle on the real chip.
+    fn get_operand_address_with_page_cross(&mut self, mode: AddressingMode) -> (u16, bool) {
+        self.watch_pointer(mode);
+        self.get_absolute_address(mode, self.program_counter)
+    }
+
+    /// Checks watchpoints on the pointer an indirect operand goes through,
+    /// since `get_absolute_address` only peeks it.
+    fn watch_pointer(&mut self, mode: AddressingMode) {
+        use self::AddressingMode::*;
+
+        let operand = self.program_counter;
+        let (lo, hi) = match mode {
+            Indirect => {
+                let ptr = self.mem_read_u16(operand);
+                (ptr, (ptr & 0xff00) | (ptr.wrapping_add(1) & 0x00ff))
+            }
+            IndirectX => {
+                let ptr = self.mem_read(operand).wrapping_add(self.register_x);
+                (ptr as u16, ptr.wrapping_add(1) as u16)
+            }
+            IndirectY => {
+                let ptr = self.mem_read(operand);
+                (ptr as u16, ptr.wrapping_add(1) as u16)
+            }
+            _ => return,
+        };
+        self.check_watchpoint(lo, false);
+        self.check_watchpoint(hi, false);
+    }
+
+    /// Resolves the effective address of an operand stored at `addr`.
+    fn get_absolute_address(&self, mode: AddressingMode, addr: u16) -> (u16, bool) {
+        use self::AddressingMode::*;
+
+        match mode {
+            Immediate => (addr, false),
+            ZeroPage => (self.mem_read(addr) as u16, false),
+            Absolute => (self.mem_read_u16(addr), false),
+
+            ZeroPageX => {
+                let base = self.mem_read(addr);
+                (base.wrapping_add(self.register_x) as u16, false)
+            }
+            ZeroPageY => {
+                let base = self.mem_read(addr);
+                (base.wrapping_add(self.register_y) as u16, false)
+            }
+
+            AbsoluteX => {
+                let base = self.mem_read_u16(addr);
+                let target = base.wrapping_add(self.register_x as u16);
+                (target, page_crossed(base, target))
+            }
+            AbsoluteY => {
+                let base = self.mem_read_u16(addr);
+                let target = base.wrapping_add(self.register_y as u16);
+                (target, page_crossed(base, target))
+            }
+
+            Indirect => {
+                let ptr = self.mem_read_u16(addr);
+                // The high byte is fetched without carrying into the page, like the real chip.
+                let lo = self.mem_read(ptr) as u16;
+                let hi = self.mem_read((ptr & 0xff00) | (ptr.wrapping_add(1) & 0x00ff)) as u16;
+                ((hi << 8) | lo, false)
+            }
+
+            IndirectX => {
+                let base = self.mem_read(addr);
+                let ptr = base.wrapping_add(self.register_x);
+                let lo = self.mem_read(ptr as u16) as u16;
+                let hi = self.mem_read(ptr.wrapping_add(1) as u16) as u16;
+                ((hi << 8) | lo, false)
+            }
+            IndirectY => {
+                let ptr = self.mem_read(addr);
+                let lo = self.mem_read(ptr as u16) as u16;
+                let hi = self.mem_read(ptr.wrapping_add(1) as u16) as u16;
+                let base = (hi << 8) | lo;
+                let target = base.wrapping_add(self.register_y as u16);
+                (target, page_crossed(base, target))
+            }
+
+            Relative => {
+                let offset = self.mem_read(addr) as i8;
+                let next = addr.wrapping_add(1);
+                (next.wrapping_add(offset as u16), false)
+            }
+
+            Accumulator | NoneAddressing => panic!("{:?} is not supported", mode),
+        }
+    }
+}
+
+fn page_crossed(a: u16, b: u16) -> bool {
+    a & 0xff00 != b & 0xff00
+}
+
+fn bcd_add(a: u8, m: u8, carry: u8) -> (u8, bool) {
+    let mut lo = (a & 0x0f) as u16 + (m & 0x0f) as u16 + carry as u16;
+    let mut hi = (a >> 4) as u16 + (m >> 4) as u16;
+    if lo > 0x09 {
+        lo += 0x06;
+        hi += 1;
+    }
+    if hi > 0x09 {
+        hi += 0x06;
+    }
+    (((hi << 4) | (lo & 0x0f)) as u8, hi > 0x0f)
+}
+
+fn bcd_sub(a: u8, m: u8, borrow: u8) -> u8 {
+    let mut lo = (a & 0x0f) as i16 - (m & 0x0f) as i16 - borrow as i16;
+    let mut hi = (a >> 4) as i16 - (m >> 4) as i16;
+    if lo < 0 {
+        lo -= 0x06;
+        hi -= 1;
+    }
+    if hi < 0 {
+        hi -= 0x06;
+    }
+    ((hi << 4) | (lo & 0x0f)) as u8
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    fn brk_address(cpu: &CPU) -> u16 {
+        let return_address = cpu.mem_read_u16(STACK + cpu.register_sp as u16 + 2);
+        return_address - 2
+    }
+
+    #[test]
+    fn test_0xa9_lda_immediate_load_data() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![0xa9, 0x05, 0x00]);
+        assert_eq!(cpu.register_a, 0x05);
+        assert!(!cpu.get_flag(ZERO));
+        assert!(!cpu.get_flag(SIGN));
+    }
+
+    #[test]
+    fn test_0xa9_lda_zero_flag() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![0xa9, 0x00, 0x00]);
+        assert!(cpu.get_flag(ZERO));
+    }
+
+    #[test]
+    fn test_0xaa_tax_move_a_to_x() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![0xa9, 0x10, 0xaa, 0x00]);
+        assert_eq!(cpu.register_x, 0x10);
+    }
+
+    #[test]
+    fn test_5_ops_working_together() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![0xa9, 0xc0, 0xaa, 0xe8, 0x00]);
+        assert_eq!(cpu.register_x, 0xc1);
+    }
+
+    #[test]
+    fn test_instruction_count() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![0xa9, 0xc0, 0xaa, 0xe8, 0x00]);
+        assert_eq!(cpu.instruction_count(), 3);
+
+        cpu.reset();
+        assert_eq!(cpu.instruction_count(), 0);
+    }
+
+    #[test]
+    fn test_inx_overflow() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![0xa9, 0xff, 0xaa, 0xe8, 0xe8, 0x00]);
+        assert_eq!(cpu.register_x, 0x1);
+    }
+
+    #[test]
+    fn test_lda_from_memory() {
+        let mut cpu = CPU::new();
+        cpu.mem_write(0x10, 0x55);
+        cpu.load_and_run(vec![0xa5, 0x10, 0x00]);
+        assert_eq!(cpu.register_a, 0x55);
+    }
+
+    #[test]
+    fn test_sta_move_a_to_memory() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![0xa9, 0x10, 0x85, 0xff, 0x00]);
+        assert_eq!(cpu.mem_read(0x00ff), 0x10);
+    }
+
+    #[test]
+    fn test_sta_indirect_x() {
+        let mut cpu = CPU::new();
+        cpu.mem_write_u16(0x24, 0x0300);
+        cpu.load_and_run(vec![0xa2, 0x04, 0xa9, 0x42, 0x81, 0x20, 0x00]);
+        assert_eq!(cpu.mem_read(0x0300), 0x42);
+        assert_eq!(cpu.mem_read(0x24), 0x00);
+    }
+
+    #[test]
+    fn test_sta_indirect_x_wraps_zero_page() {
+        // ($FF,X) with X=1 reads the pointer from 0x00-0x01, not 0x100.
+        let mut cpu = CPU::new();
+        cpu.mem_write_u16(0x00, 0x0300);
+        cpu.mem_write_u16(0x0100, 0x0400);
+        cpu.load_and_run(vec![0xa2, 0x01, 0xa9, 0x42, 0x81, 0xff, 0x00]);
+        assert_eq!(cpu.mem_read(0x0300), 0x42);
+        assert_eq!(cpu.mem_read(0x0400), 0x00);
+    }
+
+    #[test]
+    fn test_adc() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![0xa9, 0x10, 0x69, 0x05, 0x00]);
         assert_eq!(cpu.register_a, 0x15);
         assert!(!cpu.get_flag(CARRY));
         assert!(!cpu.get_flag(ZERO));
@@ -371,35 +2426,2701 @@ mod test {
     }
 
     #[test]
-    fn test_adc_overflow() {
+    fn test_builder_adc() {
+        let mut cpu = CPU::builder()
+            .a(0x10)
+            .status(CARRY)
+            .program_counter(0x8000)
+            .build();
+        cpu.load(vec![0x69, 0x05]).unwrap();
+
+        cpu.step();
+        assert_eq!(cpu.register_a, 0x16);
+        assert!(!cpu.get_flag(CARRY));
+        assert_eq!(cpu.register_sp, 0xfd);
+    }
+
+    #[test]
+    fn test_adc_overflow() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![0xa9, 0x7f, 0x69, 0x01, 0x00]);
+        assert_eq!(cpu.register_a, 0x80);
+        assert!(!cpu.get_flag(CARRY));
+        assert!(!cpu.get_flag(ZERO));
+        assert!(cpu.get_flag(OVERFLOW));
+        assert!(cpu.get_flag(SIGN));
+    }
+
+    #[test]
+    fn test_adc_carry() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![0xa9, 0xff, 0x69, 0x01, 0x00]);
+        assert_eq!(cpu.register_a, 0x00);
+        assert!(cpu.get_flag(CARRY));
+        assert!(cpu.get_flag(ZERO));
+        assert!(!cpu.get_flag(OVERFLOW));
+        assert!(!cpu.get_flag(SIGN));
+    }
+
+    #[test]
+    fn test_adc_overflow_edge_cases() {
+        // (a, m, result, carry, overflow)
+        let cases = [
+            (0x7f, 0x01, 0x80, false, true),
+            (0x80, 0xff, 0x7f, true, true),
+            (0x50, 0x50, 0xa0, false, true),
+            (0x50, 0x90, 0xe0, false, false),
+            (0xd0, 0xd0, 0xa0, true, false),
+        ];
+        for &(a, m, result, carry, overflow) in cases.iter() {
+            let mut cpu = CPU::new();
+            cpu.load_and_run(vec![0xa9, a, 0x69, m, 0x00]);
+            assert_eq!(cpu.register_a, result, "{:#04x} + {:#04x}", a, m);
+            assert_eq!(cpu.get_flag(CARRY), carry, "{:#04x} + {:#04x}", a, m);
+            assert_eq!(cpu.get_flag(OVERFLOW), overflow, "{:#04x} + {:#04x}", a, m);
+        }
+    }
+
+    #[test]
+    fn test_sbc_overflow_edge_cases() {
+        // (a, m, result, carry, overflow), with the carry set beforehand.
+        let cases = [
+            (0x50, 0xb0, 0xa0, false, true),
+            (0x80, 0x01, 0x7f, true, true),
+            (0x50, 0x30, 0x20, true, false),
+            (0x00, 0x01, 0xff, false, false),
+        ];
+        for &(a, m, result, carry, overflow) in cases.iter() {
+            let mut cpu = CPU::new();
+            cpu.load_and_run(vec![0x38, 0xa9, a, 0xe9, m, 0x00]);
+            assert_eq!(cpu.register_a, result, "{:#04x} - {:#04x}", a, m);
+            assert_eq!(cpu.get_flag(CARRY), carry, "{:#04x} - {:#04x}", a, m);
+            assert_eq!(cpu.get_flag(OVERFLOW), overflow, "{:#04x} - {:#04x}", a, m);
+        }
+    }
+
+    #[test]
+    fn test_sbc() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![0xa9, 0x10, 0xe9, 0x0f, 0x00]);
+        assert_eq!(cpu.register_a, 0x00);
+        assert!(cpu.get_flag(CARRY));
+        assert!(cpu.get_flag(ZERO));
+        assert!(!cpu.get_flag(OVERFLOW));
+        assert!(!cpu.get_flag(SIGN));
+    }
+
+    #[test]
+    fn test_sbc_borrow() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![0xa9, 0x05, 0xe9, 0x10, 0x00]);
+        assert_eq!(cpu.register_a, 0xf4);
+        assert!(!cpu.get_flag(CARRY));
+        assert!(!cpu.get_flag(ZERO));
+        assert!(!cpu.get_flag(OVERFLOW));
+        assert!(cpu.get_flag(SIGN));
+    }
+
+    #[test]
+    fn test_sbc_overflow() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![0xa9, 0x80, 0xe9, 0x01, 0x00]);
+        assert_eq!(cpu.register_a, 0x7e);
+        assert!(cpu.get_flag(CARRY));
+        assert!(!cpu.get_flag(ZERO));
+        assert!(cpu.get_flag(OVERFLOW));
+        assert!(!cpu.get_flag(SIGN));
+    }
+
+    #[test]
+    fn test_and() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![0xa9, 0x3c, 0x29, 0x0f, 0x00]);
+        assert_eq!(cpu.register_a, 0x0c);
+        assert!(!cpu.get_flag(ZERO));
+        assert!(!cpu.get_flag(SIGN));
+    }
+
+    #[test]
+    fn test_and_zero_flag() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![0xa9, 0xf0, 0x29, 0x0f, 0x00]);
+        assert_eq!(cpu.register_a, 0x00);
+        assert!(cpu.get_flag(ZERO));
+        assert!(!cpu.get_flag(SIGN));
+    }
+
+    #[test]
+    fn test_and_sign_flag() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![0xa9, 0xf0, 0x29, 0x80, 0x00]);
+        assert_eq!(cpu.register_a, 0x80);
+        assert!(!cpu.get_flag(ZERO));
+        assert!(cpu.get_flag(SIGN));
+    }
+
+    #[test]
+    fn test_ora() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![0xa9, 0x30, 0x09, 0x0c, 0x00]);
+        assert_eq!(cpu.register_a, 0x3c);
+        assert!(!cpu.get_flag(ZERO));
+        assert!(!cpu.get_flag(SIGN));
+    }
+
+    #[test]
+    fn test_ora_identity() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![0xa9, 0x5a, 0x09, 0x00, 0x00]);
+        assert_eq!(cpu.register_a, 0x5a);
+        assert!(!cpu.get_flag(ZERO));
+        assert!(!cpu.get_flag(SIGN));
+    }
+
+    #[test]
+    fn test_ora_sign_flag() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![0xa9, 0x01, 0x09, 0x80, 0x00]);
+        assert_eq!(cpu.register_a, 0x81);
+        assert!(!cpu.get_flag(ZERO));
+        assert!(cpu.get_flag(SIGN));
+    }
+
+    #[test]
+    fn test_eor() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![0xa9, 0x3c, 0x49, 0x0f, 0x00]);
+        assert_eq!(cpu.register_a, 0x33);
+        assert!(!cpu.get_flag(ZERO));
+        assert!(!cpu.get_flag(SIGN));
+    }
+
+    #[test]
+    fn test_eor_with_itself() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![0xa9, 0x5a, 0x49, 0x5a, 0x00]);
+        assert_eq!(cpu.register_a, 0x00);
+        assert!(cpu.get_flag(ZERO));
+        assert!(!cpu.get_flag(SIGN));
+    }
+
+    #[test]
+    fn test_eor_sign_flag() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![0xa9, 0x01, 0x49, 0x80, 0x00]);
+        assert_eq!(cpu.register_a, 0x81);
+        assert!(!cpu.get_flag(ZERO));
+        assert!(cpu.get_flag(SIGN));
+    }
+
+    #[test]
+    fn test_cmp_greater() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![0xa9, 0x10, 0xc9, 0x05, 0x00]);
+        assert_eq!(cpu.register_a, 0x10);
+        assert!(cpu.get_flag(CARRY));
+        assert!(!cpu.get_flag(ZERO));
+        assert!(!cpu.get_flag(SIGN));
+    }
+
+    #[test]
+    fn test_cmp_equal() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![0xa9, 0x10, 0xc9, 0x10, 0x00]);
+        assert!(cpu.get_flag(CARRY));
+        assert!(cpu.get_flag(ZERO));
+        assert!(!cpu.get_flag(SIGN));
+    }
+
+    #[test]
+    fn test_cmp_less() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![0xa9, 0x05, 0xc9, 0x10, 0x00]);
+        assert!(!cpu.get_flag(CARRY));
+        assert!(!cpu.get_flag(ZERO));
+        assert!(cpu.get_flag(SIGN));
+    }
+
+    #[test]
+    fn test_cpx_greater() {
+        let mut cpu = CPU::new();
+        cpu.load(vec![0xe0, 0x05, 0x00]).unwrap();
+        cpu.reset();
+        cpu.register_x = 0x10;
+        cpu.run();
+        assert!(cpu.get_flag(CARRY));
+        assert!(!cpu.get_flag(ZERO));
+        assert!(!cpu.get_flag(SIGN));
+    }
+
+    #[test]
+    fn test_cpx_equal() {
+        let mut cpu = CPU::new();
+        cpu.load(vec![0xe0, 0x10, 0x00]).unwrap();
+        cpu.reset();
+        cpu.register_x = 0x10;
+        cpu.run();
+        assert!(cpu.get_flag(CARRY));
+        assert!(cpu.get_flag(ZERO));
+        assert!(!cpu.get_flag(SIGN));
+    }
+
+    #[test]
+    fn test_cpx_less() {
+        let mut cpu = CPU::new();
+        cpu.load(vec![0xe0, 0x10, 0x00]).unwrap();
+        cpu.reset();
+        cpu.register_x = 0x05;
+        cpu.run();
+        assert!(!cpu.get_flag(CARRY));
+        assert!(!cpu.get_flag(ZERO));
+        assert!(cpu.get_flag(SIGN));
+    }
+
+    #[test]
+    fn test_cpy_greater() {
+        let mut cpu = CPU::new();
+        cpu.load(vec![0xc0, 0x05, 0x00]).unwrap();
+        cpu.reset();
+        cpu.register_y = 0x10;
+        cpu.run();
+        assert!(cpu.get_flag(CARRY));
+        assert!(!cpu.get_flag(ZERO));
+        assert!(!cpu.get_flag(SIGN));
+    }
+
+    #[test]
+    fn test_cpy_equal() {
+        let mut cpu = CPU::new();
+        cpu.load(vec![0xc0, 0x10, 0x00]).unwrap();
+        cpu.reset();
+        cpu.register_y = 0x10;
+        cpu.run();
+        assert!(cpu.get_flag(CARRY));
+        assert!(cpu.get_flag(ZERO));
+        assert!(!cpu.get_flag(SIGN));
+    }
+
+    #[test]
+    fn test_cpy_less() {
+        let mut cpu = CPU::new();
+        cpu.load(vec![0xc0, 0x10, 0x00]).unwrap();
+        cpu.reset();
+        cpu.register_y = 0x05;
+        cpu.run();
+        assert!(!cpu.get_flag(CARRY));
+        assert!(!cpu.get_flag(ZERO));
+        assert!(cpu.get_flag(SIGN));
+    }
+
+    #[test]
+    fn test_ldx_immediate_load_data() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![0xa2, 0x05, 0x00]);
+        assert_eq!(cpu.register_x, 0x05);
+        assert!(!cpu.get_flag(ZERO));
+        assert!(!cpu.get_flag(SIGN));
+    }
+
+    #[test]
+    fn test_ldx_zero_flag() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![0xa2, 0x00, 0x00]);
+        assert_eq!(cpu.register_x, 0x00);
+        assert!(cpu.get_flag(ZERO));
+    }
+
+    #[test]
+    fn test_ldx_sign_flag() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![0xa2, 0x80, 0x00]);
+        assert_eq!(cpu.register_x, 0x80);
+        assert!(cpu.get_flag(SIGN));
+    }
+
+    #[test]
+    fn test_ldy_immediate_load_data() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![0xa0, 0x05, 0x00]);
+        assert_eq!(cpu.register_y, 0x05);
+        assert!(!cpu.get_flag(ZERO));
+        assert!(!cpu.get_flag(SIGN));
+    }
+
+    #[test]
+    fn test_ldy_zero_flag() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![0xa0, 0x00, 0x00]);
+        assert_eq!(cpu.register_y, 0x00);
+        assert!(cpu.get_flag(ZERO));
+    }
+
+    #[test]
+    fn test_ldy_sign_flag() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![0xa0, 0x80, 0x00]);
+        assert_eq!(cpu.register_y, 0x80);
+        assert!(cpu.get_flag(SIGN));
+    }
+
+    #[test]
+    fn test_ldx_from_memory_zero_page_y() {
+        let mut cpu = CPU::new();
+        cpu.mem_write(0x15, 0x55);
+        cpu.load_and_run(vec![0xa0, 0x05, 0xb6, 0x10, 0x00]);
+        assert_eq!(cpu.register_x, 0x55);
+    }
+
+    #[test]
+    fn test_stx_move_x_to_memory() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![0xa2, 0x10, 0x86, 0xff, 0x00]);
+        assert_eq!(cpu.mem_read(0x00ff), 0x10);
+    }
+
+    #[test]
+    fn test_sty_move_y_to_memory() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![0xa0, 0x10, 0x8c, 0x00, 0x02, 0x00]);
+        assert_eq!(cpu.mem_read(0x0200), 0x10);
+    }
+
+    #[test]
+    fn test_0xa8_tay_move_a_to_y() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![0xa9, 0x10, 0xa8, 0x00]);
+        assert_eq!(cpu.register_y, 0x10);
+        assert!(!cpu.get_flag(ZERO));
+        assert!(!cpu.get_flag(SIGN));
+    }
+
+    #[test]
+    fn test_0x8a_txa_move_x_to_a() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![0xa2, 0x10, 0x8a, 0x00]);
+        assert_eq!(cpu.register_a, 0x10);
+    }
+
+    #[test]
+    fn test_0x98_tya_move_y_to_a() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![0xa0, 0x10, 0x98, 0x00]);
+        assert_eq!(cpu.register_a, 0x10);
+    }
+
+    #[test]
+    fn test_tay_zero_flag() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![0xa0, 0x10, 0xa9, 0x00, 0xa8, 0x00]);
+        assert_eq!(cpu.register_y, 0x00);
+        assert!(cpu.get_flag(ZERO));
+    }
+
+    #[test]
+    fn test_txa_sign_flag() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![0xa2, 0x80, 0xa9, 0x00, 0x8a, 0x00]);
+        assert_eq!(cpu.register_a, 0x80);
+        assert!(!cpu.get_flag(ZERO));
+        assert!(cpu.get_flag(SIGN));
+    }
+
+    #[test]
+    fn test_tya_zero_flag() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![0xa9, 0x10, 0xa0, 0x00, 0x98, 0x00]);
+        assert_eq!(cpu.register_a, 0x00);
+        assert!(cpu.get_flag(ZERO));
+    }
+
+    #[test]
+    fn test_stack_pointer_after_reset() {
+        let mut cpu = CPU::new();
+        cpu.register_sp = 0x00;
+        cpu.load(vec![0x00]).unwrap();
+        cpu.reset();
+        assert_eq!(cpu.register_sp, 0xfd);
+    }
+
+    #[test]
+    fn test_0xba_tsx_move_sp_to_x() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![0xba, 0x00]);
+        assert_eq!(cpu.register_x, 0xfd);
+        assert!(!cpu.get_flag(ZERO));
+        assert!(cpu.get_flag(SIGN));
+    }
+
+    #[test]
+    fn test_0x9a_txs_move_x_to_sp() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![0xa2, 0x40, 0x9a, 0x00]);
+        assert_eq!(cpu.register_sp, 0x40 - 3);
+        assert!(!cpu.get_flag(ZERO));
+    }
+
+    #[test]
+    fn test_0x48_pha_push_a() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![0xa9, 0x42, 0x48, 0x00]);
+        assert_eq!(cpu.register_sp, 0xfc - 3);
+        assert_eq!(cpu.mem_read(0x01fd), 0x42);
+    }
+
+    #[test]
+    fn test_pha_pla_lifo_order() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![
+            0xa9, 0x01, 0x48, 0xa9, 0x02, 0x48, 0xa9, 0x03, 0x48, // push 1, 2, 3
+            0x68, 0xaa, 0x68, 0xa8, 0x68, 0x00, // pull into X, Y, A
+        ]);
+        assert_eq!(cpu.register_x, 0x03);
+        assert_eq!(cpu.register_y, 0x02);
+        assert_eq!(cpu.register_a, 0x01);
+        assert_eq!(cpu.register_sp, 0xfd - 3);
+    }
+
+    #[test]
+    fn test_pla_zero_flag() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![0xa9, 0x00, 0x48, 0xa9, 0x10, 0x68, 0x00]);
+        assert_eq!(cpu.register_a, 0x00);
+        assert!(cpu.get_flag(ZERO));
+    }
+
+    #[test]
+    fn test_stack_pointer_wraps() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![0xa2, 0x00, 0x9a, 0xa9, 0x42, 0x48, 0x00]);
+        assert_eq!(cpu.register_sp, 0xff - 3);
+        assert_eq!(cpu.mem_read(0x0100), 0x42);
+
+        cpu.load_and_run(vec![0xa2, 0xff, 0x9a, 0x68, 0x00]);
+        assert_eq!(cpu.register_sp, 0xfd);
+        assert_eq!(cpu.register_a, 0x42);
+    }
+
+    #[test]
+    fn test_0x08_php_push_status() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![0xa9, 0xff, 0x69, 0x01, 0x08, 0x00]);
+        assert_eq!(cpu.register_sp, 0xfc - 3);
+        assert_eq!(
+            cpu.mem_read(0x01fd),
+            BREAK | UNUSED | INTERRUPT_DISABLE | CARRY | ZERO
+        );
+    }
+
+    #[test]
+    fn test_php_plp_restore_status() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![
+            0xa9, 0xff, 0x69, 0x01, 0x08, // set CARRY and ZERO, then push
+            0xa9, 0x01, 0x69, 0x00, // clear CARRY and ZERO
+            0x28, 0x00,
+        ]);
+        assert_eq!(cpu.register_a, 0x02);
+        assert!(cpu.get_flag(CARRY));
+        assert!(cpu.get_flag(ZERO));
+        assert!(!cpu.get_flag(BREAK));
+        assert!(cpu.get_flag(UNUSED));
+    }
+
+    #[test]
+    fn test_inc_memory() {
+        let mut cpu = CPU::new();
+        cpu.mem_write(0x10, 0x41);
+        cpu.load_and_run(vec![0xe6, 0x10, 0x00]);
+        assert_eq!(cpu.mem_read(0x10), 0x42);
+        assert!(!cpu.get_flag(ZERO));
+        assert!(!cpu.get_flag(SIGN));
+    }
+
+    #[test]
+    fn test_inc_overflow() {
+        let mut cpu = CPU::new();
+        cpu.mem_write(0x10, 0xff);
+        cpu.load_and_run(vec![0xe6, 0x10, 0x00]);
+        assert_eq!(cpu.mem_read(0x10), 0x00);
+        assert!(cpu.get_flag(ZERO));
+        assert!(!cpu.get_flag(SIGN));
+    }
+
+    #[test]
+    fn test_dec_memory() {
+        let mut cpu = CPU::new();
+        cpu.mem_write(0x0210, 0x42);
+        cpu.load_and_run(vec![0xa2, 0x10, 0xde, 0x00, 0x02, 0x00]);
+        assert_eq!(cpu.mem_read(0x0210), 0x41);
+        assert!(!cpu.get_flag(ZERO));
+        assert!(!cpu.get_flag(SIGN));
+    }
+
+    #[test]
+    fn test_dec_underflow() {
+        let mut cpu = CPU::new();
+        cpu.mem_write(0x10, 0x00);
+        cpu.load_and_run(vec![0xc6, 0x10, 0x00]);
+        assert_eq!(cpu.mem_read(0x10), 0xff);
+        assert!(!cpu.get_flag(ZERO));
+        assert!(cpu.get_flag(SIGN));
+    }
+
+    #[test]
+    fn test_iny_overflow() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![0xa0, 0xff, 0xc8, 0x00]);
+        assert_eq!(cpu.register_y, 0x00);
+        assert!(cpu.get_flag(ZERO));
+        assert!(!cpu.get_flag(SIGN));
+    }
+
+    #[test]
+    fn test_dex_underflow() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![0xa2, 0x00, 0xca, 0x00]);
+        assert_eq!(cpu.register_x, 0xff);
+        assert!(!cpu.get_flag(ZERO));
+        assert!(cpu.get_flag(SIGN));
+    }
+
+    #[test]
+    fn test_dey_underflow() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![0xa0, 0x00, 0x88, 0x00]);
+        assert_eq!(cpu.register_y, 0xff);
+        assert!(!cpu.get_flag(ZERO));
+        assert!(cpu.get_flag(SIGN));
+    }
+
+    #[test]
+    fn test_dex_dey_to_zero() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![0xa2, 0x01, 0xa0, 0x02, 0xca, 0x88, 0x88, 0x00]);
+        assert_eq!(cpu.register_x, 0x00);
+        assert_eq!(cpu.register_y, 0x00);
+        assert!(cpu.get_flag(ZERO));
+    }
+
+    #[test]
+    fn test_asl_accumulator() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![0xa9, 0x21, 0x0a, 0x00]);
+        assert_eq!(cpu.register_a, 0x42);
+        assert!(!cpu.get_flag(CARRY));
+        assert!(!cpu.get_flag(ZERO));
+        assert!(!cpu.get_flag(SIGN));
+    }
+
+    #[test]
+    fn test_asl_accumulator_carry() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![0xa9, 0x80, 0x0a, 0x00]);
+        assert_eq!(cpu.register_a, 0x00);
+        assert!(cpu.get_flag(CARRY));
+        assert!(cpu.get_flag(ZERO));
+        assert!(!cpu.get_flag(SIGN));
+    }
+
+    #[test]
+    fn test_asl_memory() {
+        let mut cpu = CPU::new();
+        cpu.mem_write(0x10, 0xc1);
+        cpu.load_and_run(vec![0x06, 0x10, 0x00]);
+        assert_eq!(cpu.mem_read(0x10), 0x82);
+        assert!(cpu.get_flag(CARRY));
+        assert!(!cpu.get_flag(ZERO));
+        assert!(cpu.get_flag(SIGN));
+    }
+
+    #[test]
+    fn test_lsr_accumulator() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![0xa9, 0x84, 0x4a, 0x00]);
+        assert_eq!(cpu.register_a, 0x42);
+        assert!(!cpu.get_flag(CARRY));
+        assert!(!cpu.get_flag(ZERO));
+        assert!(!cpu.get_flag(SIGN));
+    }
+
+    #[test]
+    fn test_lsr_accumulator_carry() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![0xa9, 0x85, 0x4a, 0x00]);
+        assert_eq!(cpu.register_a, 0x42);
+        assert!(cpu.get_flag(CARRY));
+        assert!(!cpu.get_flag(SIGN));
+    }
+
+    #[test]
+    fn test_lsr_memory_zero_flag() {
+        let mut cpu = CPU::new();
+        cpu.mem_write(0x10, 0x01);
+        cpu.load_and_run(vec![0x46, 0x10, 0x00]);
+        assert_eq!(cpu.mem_read(0x10), 0x00);
+        assert!(cpu.get_flag(CARRY));
+        assert!(cpu.get_flag(ZERO));
+        assert!(!cpu.get_flag(SIGN));
+    }
+
+    #[test]
+    fn test_rol_accumulator_carry_clear() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![0xa9, 0x80, 0x2a, 0x00]);
+        assert_eq!(cpu.register_a, 0x00);
+        assert!(cpu.get_flag(CARRY));
+        assert!(cpu.get_flag(ZERO));
+    }
+
+    #[test]
+    fn test_rol_accumulator_carry_set() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![0xa9, 0xff, 0x69, 0x01, 0xa9, 0x40, 0x2a, 0x00]);
+        assert_eq!(cpu.register_a, 0x81);
+        assert!(!cpu.get_flag(CARRY));
+        assert!(cpu.get_flag(SIGN));
+    }
+
+    #[test]
+    fn test_rol_memory() {
+        let mut cpu = CPU::new();
+        cpu.mem_write(0x10, 0xc0);
+        cpu.load_and_run(vec![0x26, 0x10, 0x00]);
+        assert_eq!(cpu.mem_read(0x10), 0x80);
+        assert!(cpu.get_flag(CARRY));
+        assert!(cpu.get_flag(SIGN));
+    }
+
+    #[test]
+    fn test_ror_accumulator_carry_clear() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![0xa9, 0x01, 0x6a, 0x00]);
+        assert_eq!(cpu.register_a, 0x00);
+        assert!(cpu.get_flag(CARRY));
+        assert!(cpu.get_flag(ZERO));
+    }
+
+    #[test]
+    fn test_ror_accumulator_carry_set() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![0xa9, 0xff, 0x69, 0x01, 0xa9, 0x02, 0x6a, 0x00]);
+        assert_eq!(cpu.register_a, 0x81);
+        assert!(!cpu.get_flag(CARRY));
+        assert!(cpu.get_flag(SIGN));
+    }
+
+    #[test]
+    fn test_ror_memory() {
+        let mut cpu = CPU::new();
+        cpu.mem_write(0x10, 0x03);
+        cpu.load_and_run(vec![0x66, 0x10, 0x00]);
+        assert_eq!(cpu.mem_read(0x10), 0x01);
+        assert!(cpu.get_flag(CARRY));
+        assert!(!cpu.get_flag(SIGN));
+    }
+
+    #[test]
+    fn test_relative_address_forward() {
+        let mut cpu = CPU::new();
+        cpu.program_counter = 0x8001;
+        cpu.mem_write(0x8001, 0x05);
+        assert_eq!(cpu.get_operand_address(AddressingMode::Relative), 0x8007);
+    }
+
+    #[test]
+    fn test_relative_address_backward() {
+        let mut cpu = CPU::new();
+        cpu.program_counter = 0x8001;
+        cpu.mem_write(0x8001, 0xfb);
+        assert_eq!(cpu.get_operand_address(AddressingMode::Relative), 0x7ffd);
+
+        cpu.program_counter = 0x0000;
+        cpu.mem_write(0x0000, 0xfd);
+        assert_eq!(cpu.get_operand_address(AddressingMode::Relative), 0xfffe);
+    }
+
+    #[test]
+    fn test_beq_taken() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![0xa9, 0x00, 0xf0, 0x02, 0xa2, 0x01, 0x00]);
+        assert_eq!(cpu.register_x, 0x00);
+    }
+
+    #[test]
+    fn test_beq_not_taken() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![0xa9, 0x01, 0xf0, 0x02, 0xa2, 0x01, 0x00]);
+        assert_eq!(cpu.register_x, 0x01);
+    }
+
+    #[test]
+    fn test_bne_taken() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![0xa9, 0x01, 0xd0, 0x02, 0xa2, 0x01, 0x00]);
+        assert_eq!(cpu.register_x, 0x00);
+    }
+
+    #[test]
+    fn test_bne_countdown_loop() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![
+            0xa2, 0x05, // LDX #$05
+            0xc8, // loop: INY
+            0xca, // DEX
+            0xd0, 0xfc, // BNE loop
+            0x00,
+        ]);
+        assert_eq!(cpu.register_x, 0x00);
+        assert_eq!(cpu.register_y, 0x05);
+        assert!(cpu.get_flag(ZERO));
+    }
+
+    #[test]
+    fn test_bcc_taken() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![0x90, 0x01, 0x00, 0x00]);
+        assert_eq!(brk_address(&cpu), 0x8003);
+    }
+
+    #[test]
+    fn test_bcc_not_taken() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![0xa9, 0xff, 0x69, 0x01, 0x90, 0x01, 0x00, 0x00]);
+        assert_eq!(brk_address(&cpu), 0x8006);
+    }
+
+    #[test]
+    fn test_bcs_taken() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![0xa9, 0xff, 0x69, 0x01, 0xb0, 0x01, 0x00, 0x00]);
+        assert_eq!(brk_address(&cpu), 0x8007);
+    }
+
+    #[test]
+    fn test_bcs_not_taken() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![0xb0, 0x01, 0x00, 0x00]);
+        assert_eq!(brk_address(&cpu), 0x8002);
+    }
+
+    #[test]
+    fn test_bvc_taken() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![0x50, 0x01, 0x00, 0x00]);
+        assert_eq!(brk_address(&cpu), 0x8003);
+    }
+
+    #[test]
+    fn test_bvc_not_taken() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![0xa9, 0x7f, 0x69, 0x01, 0x50, 0x01, 0x00, 0x00]);
+        assert_eq!(brk_address(&cpu), 0x8006);
+    }
+
+    #[test]
+    fn test_bvs_taken() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![0xa9, 0x7f, 0x69, 0x01, 0x70, 0x01, 0x00, 0x00]);
+        assert_eq!(brk_address(&cpu), 0x8007);
+    }
+
+    #[test]
+    fn test_bvs_not_taken() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![0x70, 0x01, 0x00, 0x00]);
+        assert_eq!(brk_address(&cpu), 0x8002);
+    }
+
+    #[test]
+    fn test_bmi_taken_on_negative() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![0xa9, 0x80, 0x30, 0x02, 0xa2, 0x01, 0x00]);
+        assert_eq!(cpu.register_x, 0x00);
+    }
+
+    #[test]
+    fn test_bmi_not_taken_on_positive() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![0xa9, 0x7f, 0x30, 0x02, 0xa2, 0x01, 0x00]);
+        assert_eq!(cpu.register_x, 0x01);
+    }
+
+    #[test]
+    fn test_bpl_taken_on_positive() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![0xa9, 0x7f, 0x10, 0x02, 0xa2, 0x01, 0x00]);
+        assert_eq!(cpu.register_x, 0x00);
+    }
+
+    #[test]
+    fn test_bpl_not_taken_on_negative() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![0xa9, 0x80, 0x10, 0x02, 0xa2, 0x01, 0x00]);
+        assert_eq!(cpu.register_x, 0x01);
+    }
+
+    #[test]
+    fn test_jmp_absolute() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![0x4c, 0x06, 0x80, 0xa2, 0x01, 0x00, 0xa0, 0x02, 0x00]);
+        assert_eq!(cpu.register_x, 0x00);
+        assert_eq!(cpu.register_y, 0x02);
+    }
+
+    #[test]
+    fn test_jmp_indirect() {
+        let mut cpu = CPU::new();
+        cpu.mem_write_u16(0x0120, 0x8006);
+        cpu.load_and_run(vec![0x6c, 0x20, 0x01, 0xa2, 0x01, 0x00, 0xa0, 0x02, 0x00]);
+        assert_eq!(cpu.register_x, 0x00);
+        assert_eq!(cpu.register_y, 0x02);
+    }
+
+    #[test]
+    fn test_jmp_indirect_page_boundary_bug() {
+        let mut cpu = CPU::new();
+        cpu.mem_write(0x10ff, 0x06);
+        cpu.mem_write(0x1000, 0x80);
+        cpu.mem_write(0x1100, 0x90);
+        cpu.load_and_run(vec![0x6c, 0xff, 0x10, 0xa2, 0x01, 0x00, 0xa0, 0x02, 0x00]);
+        assert_eq!(cpu.register_x, 0x00);
+        assert_eq!(cpu.register_y, 0x02);
+    }
+
+    #[test]
+    fn test_0x40_rti_restore_status_and_pc() {
+        let mut cpu = CPU::new();
+        cpu.load(vec![0x40, 0x00, 0x00, 0x00, 0x00, 0xa2, 0x42, 0x00])
+            .unwrap();
+        cpu.reset();
+        cpu.stack_push(0x80);
+        cpu.stack_push(0x05);
+        cpu.stack_push(CARRY | OVERFLOW | BREAK);
+        cpu.run();
+        assert_eq!(cpu.register_x, 0x42);
+        assert_eq!(cpu.register_sp, 0xfd - 3);
+        assert!(cpu.get_flag(CARRY));
+        assert!(cpu.get_flag(OVERFLOW));
+        assert!(!cpu.get_flag(BREAK));
+        assert!(cpu.get_flag(UNUSED));
+    }
+
+    #[test]
+    fn test_bit_copies_memory_bits() {
+        let mut cpu = CPU::new();
+        cpu.mem_write(0x10, 0xc0);
+        cpu.load_and_run(vec![0xa9, 0xff, 0x24, 0x10, 0x00]);
+        assert_eq!(cpu.register_a, 0xff);
+        assert!(!cpu.get_flag(ZERO));
+        assert!(cpu.get_flag(OVERFLOW));
+        assert!(cpu.get_flag(SIGN));
+    }
+
+    #[test]
+    fn test_bit_zero_flag() {
+        let mut cpu = CPU::new();
+        cpu.mem_write(0x0210, 0xc0);
+        cpu.load_and_run(vec![0xa9, 0x3f, 0x2c, 0x10, 0x02, 0x00]);
+        assert_eq!(cpu.register_a, 0x3f);
+        assert!(cpu.get_flag(ZERO));
+        assert!(cpu.get_flag(OVERFLOW));
+        assert!(cpu.get_flag(SIGN));
+    }
+
+    #[test]
+    fn test_bit_clears_flags() {
+        let mut cpu = CPU::new();
+        cpu.mem_write(0x10, 0x01);
+        cpu.load_and_run(vec![0xa9, 0x80, 0x24, 0x10, 0x00]);
+        assert!(cpu.get_flag(ZERO));
+        assert!(!cpu.get_flag(OVERFLOW));
+        assert!(!cpu.get_flag(SIGN));
+    }
+
+    #[test]
+    fn test_0x38_sec_set_carry() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![0x38, 0x00]);
+        assert!(cpu.get_flag(CARRY));
+    }
+
+    #[test]
+    fn test_0x18_clc_clear_carry() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![0x38, 0x18, 0x00]);
+        assert!(!cpu.get_flag(CARRY));
+    }
+
+    #[test]
+    fn test_0xb8_clv_clear_overflow() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![0xa9, 0x7f, 0x69, 0x01, 0xb8, 0x00]);
+        assert!(!cpu.get_flag(OVERFLOW));
+        assert!(cpu.get_flag(SIGN));
+    }
+
+    #[test]
+    fn test_sec_adc_carry_in() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![0x38, 0xa9, 0x10, 0x69, 0x05, 0x00]);
+        assert_eq!(cpu.register_a, 0x16);
+        assert!(!cpu.get_flag(CARRY));
+    }
+
+    #[test]
+    fn test_0xf8_sed_set_decimal() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![0xf8, 0x00]);
+        assert!(cpu.get_flag(DECIMAL));
+        assert_eq!(cpu.status & DECIMAL, 0b0000_1000);
+    }
+
+    #[test]
+    fn test_0xd8_cld_clear_decimal() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![0xf8, 0xd8, 0x00]);
+        assert!(!cpu.get_flag(DECIMAL));
+    }
+
+    #[test]
+    fn test_0x78_sei_set_interrupt_disable() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![0x78, 0x00]);
+        assert!(cpu.get_flag(INTERRUPT_DISABLE));
+        assert_eq!(cpu.status & INTERRUPT_DISABLE, 0b0000_0100);
+    }
+
+    #[test]
+    fn test_0x58_cli_clear_interrupt_disable() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![0x78, 0x58, 0x08, 0x00]);
+        assert_eq!(cpu.mem_read(0x01fd) & INTERRUPT_DISABLE, 0);
+    }
+
+    #[test]
+    fn test_brk_jumps_to_irq_handler() {
+        let mut cpu = CPU::new();
+        cpu.load(vec![0xa2, 0x01, 0x00, 0x00, 0xa2, 0x42, 0x00])
+            .unwrap();
+        cpu.mem_write_u16(0xfffe, 0x8004);
+        cpu.reset();
+
+        cpu.run();
+        assert_eq!(cpu.program_counter, 0x8004);
+        assert_eq!(cpu.register_sp, 0xfa);
+        assert_eq!(cpu.mem_read_u16(0x01fc), 0x8004);
+        assert_eq!(cpu.mem_read(0x01fb), BREAK | UNUSED | INTERRUPT_DISABLE);
+        assert!(cpu.get_flag(INTERRUPT_DISABLE));
+
+        cpu.run();
+        assert_eq!(cpu.register_x, 0x42);
+    }
+
+    #[test]
+    fn test_0xea_nop() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![0xa9, 0x42, 0xea, 0xa2, 0x01, 0x00]);
+        assert_eq!(cpu.register_a, 0x42);
+        assert_eq!(cpu.register_x, 0x01);
+    }
+
+    #[test]
+    fn test_nop_unofficial_zero_page() {
+        let mut cpu = CPU::new();
+        cpu.mem_write(0x10, 0xff);
+        cpu.load_and_run(vec![0xa9, 0x42, 0x04, 0x10, 0xa2, 0x01, 0x00]);
+        assert_eq!(cpu.register_a, 0x42);
+        assert_eq!(cpu.register_x, 0x01);
+        assert_eq!(cpu.register_y, 0x00);
+        assert!(!cpu.get_flag(ZERO));
+        assert!(!cpu.get_flag(SIGN));
+    }
+
+    #[test]
+    fn test_nop_unofficial_absolute() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![0xa9, 0x42, 0x0c, 0x00, 0x02, 0xa2, 0x01, 0x00]);
+        assert_eq!(cpu.register_a, 0x42);
+        assert_eq!(cpu.register_x, 0x01);
+        assert_eq!(brk_address(&cpu), 0x8007);
+    }
+
+    #[test]
+    fn test_opcode_table_lookup() {
+        for op in CPU_OPCODES {
+            let entry = OPCODE_TABLE[op.opcode as usize].unwrap();
+            assert_eq!(entry.opcode, op.opcode);
+            assert_eq!(entry.bytes, op.bytes);
+        }
+
+        assert!(OPCODE_TABLE[0x02].is_none());
+        assert!(OPCODE_TABLE[0xf2].is_none());
+    }
+
+    #[test]
+    fn test_top_of_memory_is_addressable() {
+        let mut cpu = CPU::new();
+        cpu.mem_write(0xffff, 0x55);
+        assert_eq!(cpu.mem_read(0xffff), 0x55);
+
+        cpu.mem_write_u16(0xfffe, 0x1234);
+        assert_eq!(cpu.mem_read_u16(0xfffe), 0x1234);
+        assert_eq!(cpu.mem_read(0xffff), 0x12);
+    }
+
+    #[test]
+    fn test_indirect_y_pointer_wraps_in_zero_page() {
+        let mut cpu = CPU::new();
+        cpu.mem_write(0xff, 0x00);
+        cpu.mem_write(0x00, 0x02);
+        cpu.mem_write(0x0100, 0x03);
+        cpu.mem_write(0x0205, 0x42);
+        cpu.load_and_run(vec![0xa0, 0x05, 0xb1, 0xff, 0x00]);
+        assert_eq!(cpu.register_a, 0x42);
+    }
+
+    #[test]
+    fn test_indirect_y_page_cross() {
+        let mut cpu = CPU::new();
+        cpu.mem_write_u16(0x10, 0x02f0);
+        cpu.program_counter = 0x8000;
+        cpu.mem_write(0x8000, 0x10);
+
+        cpu.register_y = 0x0f;
+        let (addr, crossed) = cpu.get_operand_address_with_page_cross(AddressingMode::IndirectY);
+        assert_eq!(addr, 0x02ff);
+        assert!(!crossed);
+
+        cpu.register_y = 0x10;
+        let (addr, crossed) = cpu.get_operand_address_with_page_cross(AddressingMode::IndirectY);
+        assert_eq!(addr, 0x0300);
+        assert!(crossed);
+
+        cpu.mem_write_u16(0x10, 0xfff0);
+        let (addr, crossed) = cpu.get_operand_address_with_page_cross(AddressingMode::IndirectY);
+        assert_eq!(addr, 0x0000);
+        assert!(crossed);
+    }
+
+    #[test]
+    fn test_indirect_x_pointer_wraps_in_zero_page() {
+        let mut cpu = CPU::new();
+        cpu.mem_write_u16(0x02, 0x0210);
+        cpu.mem_write(0x0210, 0x42);
+        cpu.load_and_run(vec![0xa2, 0x04, 0xa1, 0xfe, 0x00]);
+        assert_eq!(cpu.register_a, 0x42);
+    }
+
+    #[test]
+    fn test_indirect_x_pointer_high_byte_wraps() {
+        let mut cpu = CPU::new();
+        cpu.mem_write(0xff, 0x10);
+        cpu.mem_write(0x00, 0x02);
+        cpu.mem_write(0x0100, 0x03);
+        cpu.mem_write(0x0210, 0x42);
+        cpu.load_and_run(vec![0xa2, 0x01, 0xa1, 0xfe, 0x00]);
+        assert_eq!(cpu.register_a, 0x42);
+    }
+
+    #[test]
+    fn test_u16_access_wraps_at_top_of_memory() {
+        let mut cpu = CPU::new();
+        cpu.mem_write_u16(0xffff, 0x1234);
+        assert_eq!(cpu.mem_read(0xffff), 0x34);
+        assert_eq!(cpu.mem_read(0x0000), 0x12);
+        assert_eq!(cpu.mem_read_u16(0xffff), 0x1234);
+    }
+
+    #[test]
+    fn test_step() {
+        let mut cpu = CPU::new();
+        cpu.load(vec![0xa9, 0xc0, 0xaa, 0xe8, 0x00]).unwrap();
+        cpu.reset();
+
+        let result = cpu.step();
+        assert_eq!(
+            result,
+            StepResult {
+                stop: None,
+                cycles: 2
+            }
+        );
+        assert_eq!(cpu.register_a, 0xc0);
+        assert_eq!(cpu.program_counter, 0x8002);
+
+        cpu.step();
+        assert_eq!(cpu.register_x, 0xc0);
+        assert_eq!(cpu.program_counter, 0x8003);
+
+        cpu.step();
+        assert_eq!(cpu.register_x, 0xc1);
+        assert_eq!(cpu.program_counter, 0x8004);
+
+        let result = cpu.step();
+        assert_eq!(
+            result,
+            StepResult {
+                stop: Some(StopReason::Break),
+                cycles: 7
+            }
+        );
+    }
+
+    #[test]
+    fn test_run_with_callback() {
+        let mut cpu = CPU::new();
+        cpu.load(vec![0xa2, 0x02, 0xca, 0xd0, 0xfd, 0x00]).unwrap();
+        cpu.reset();
+
+        let mut trace = Vec::new();
+        cpu.run_with_callback(|cpu| trace.push(cpu.program_counter));
+        assert_eq!(trace, vec![0x8000, 0x8002, 0x8003, 0x8002, 0x8003, 0x8005]);
+    }
+
+    #[test]
+    fn test_total_cycles() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![0xa9, 0xc0, 0xaa, 0xe8, 0x85, 0x10, 0x00]);
+        assert_eq!(cpu.total_cycles(), 2 + 2 + 2 + 3 + 7);
+    }
+
+    #[test]
+    fn test_absolute_x_page_cross_penalty() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![0xa2, 0x0f, 0xbd, 0xf0, 0x12, 0x00]);
+        assert_eq!(cpu.total_cycles(), 2 + 4 + 7);
+
+        cpu.load_and_run(vec![0xa2, 0x10, 0xbd, 0xf0, 0x12, 0x00]);
+        assert_eq!(cpu.total_cycles(), 2 + 5 + 7);
+    }
+
+    #[test]
+    fn test_absolute_y_page_cross_penalty() {
+        let mut cpu = CPU::new();
+        cpu.load(vec![0xa0, 0x01, 0x59, 0xff, 0x12, 0x00]).unwrap();
+        cpu.reset();
+        cpu.step();
+        assert_eq!(cpu.step().cycles, 5);
+    }
+
+    #[test]
+    fn test_indirect_y_page_cross_penalty() {
+        let mut cpu = CPU::new();
+        cpu.mem_write_u16(0x10, 0x12f0);
+        cpu.load(vec![0xa0, 0x0f, 0xb1, 0x10, 0xa0, 0x10, 0xb1, 0x10, 0x00])
+            .unwrap();
+        cpu.reset();
+        cpu.step();
+        assert_eq!(cpu.step().cycles, 5);
+        cpu.step();
+        assert_eq!(cpu.step().cycles, 6);
+    }
+
+    #[test]
+    fn test_store_has_no_page_cross_penalty() {
+        // STA $12F0,X on the same page, then across it.
+        for &x in [0x0f, 0x10].iter() {
+            let mut cpu = CPU::new();
+            cpu.load(vec![0xa2, x, 0x9d, 0xf0, 0x12, 0x00]).unwrap();
+            cpu.reset();
+            cpu.step();
+            assert_eq!(cpu.step().cycles, 5);
+        }
+
+        let mut cpu = CPU::new();
+        cpu.mem_write_u16(0x10, 0x12f0);
+        cpu.load(vec![0xa0, 0x10, 0x91, 0x10, 0x00]).unwrap();
+        cpu.reset();
+        cpu.step();
+        assert_eq!(cpu.step().cycles, 6);
+    }
+
+    #[test]
+    fn test_branch_not_taken_cycles() {
+        let mut cpu = CPU::new();
+        cpu.load(vec![0xa9, 0x01, 0xf0, 0x02, 0x00]).unwrap();
+        cpu.reset();
+        cpu.step();
+        assert_eq!(cpu.step().cycles, 2);
+    }
+
+    #[test]
+    fn test_branch_taken_same_page_cycles() {
+        let mut cpu = CPU::new();
+        cpu.load(vec![0xa9, 0x00, 0xf0, 0x02, 0x00]).unwrap();
+        cpu.reset();
+        cpu.step();
+        assert_eq!(cpu.step().cycles, 3);
+    }
+
+    #[test]
+    fn test_branch_taken_cross_page_cycles() {
+        let mut cpu = CPU::new();
+        cpu.load(vec![0xa9, 0x00, 0xf0, 0xf0, 0x00]).unwrap();
+        cpu.reset();
+        cpu.step();
+        assert_eq!(cpu.step().cycles, 4);
+        assert_eq!(cpu.program_counter, 0x7ff4);
+    }
+
+    #[test]
+    fn test_adc_decimal() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![0xf8, 0x18, 0xa9, 0x09, 0x69, 0x01, 0x00]);
+        assert_eq!(cpu.register_a, 0x10);
+        assert!(!cpu.get_flag(CARRY));
+    }
+
+    #[test]
+    fn test_adc_decimal_carry() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![0xf8, 0x38, 0xa9, 0x58, 0x69, 0x46, 0x00]);
+        assert_eq!(cpu.register_a, 0x05);
+        assert!(cpu.get_flag(CARRY));
+    }
+
+    #[test]
+    fn test_sbc_decimal() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![0xf8, 0x38, 0xa9, 0x46, 0xe9, 0x12, 0x00]);
+        assert_eq!(cpu.register_a, 0x34);
+        assert!(cpu.get_flag(CARRY));
+    }
+
+    #[test]
+    fn test_sbc_decimal_borrow() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![0xf8, 0x38, 0xa9, 0x12, 0xe9, 0x21, 0x00]);
+        assert_eq!(cpu.register_a, 0x91);
+        assert!(!cpu.get_flag(CARRY));
+        assert!(cpu.get_flag(SIGN));
+    }
+
+    #[test]
+    fn test_decimal_disabled() {
+        let mut cpu = CPU::new();
+        cpu.decimal_enabled = false;
+        cpu.load_and_run(vec![0xf8, 0x18, 0xa9, 0x09, 0x69, 0x01, 0x00]);
+        assert_eq!(cpu.register_a, 0x0a);
+    }
+
+    fn ines(prg_rom: &[u8]) -> Vec<u8> {
+        let mut data = vec![0x4e, 0x45, 0x53, 0x1a, (prg_rom.len() / 0x4000) as u8, 0];
+        data.resize(16, 0);
+        data.extend_from_slice(prg_rom);
+        data
+    }
+
+    #[test]
+    fn test_load_rom_16kb() {
+        let mut prg_rom = vec![0; 0x4000];
+        prg_rom[0x0000] = 0x42;
+        prg_rom[0x3ffc] = 0x00;
+        prg_rom[0x3ffd] = 0x80;
+
+        let mut cpu = CPU::new();
+        cpu.load_rom(&ines(&prg_rom)).unwrap();
+        assert_eq!(cpu.mem_read(0x8000), 0x42);
+        assert_eq!(cpu.mem_read(0xc000), 0x42);
+        assert_eq!(cpu.mem_read_u16(0xbffc), 0x8000);
+        assert_eq!(cpu.mem_read_u16(0xfffc), 0x8000);
+    }
+
+    #[test]
+    fn test_load_rom_32kb() {
+        let mut prg_rom = vec![0; 0x8000];
+        prg_rom[0x0000] = 0x42;
+        prg_rom[0x4000] = 0x43;
+        prg_rom[0x7ffc] = 0x00;
+        prg_rom[0x7ffd] = 0xc0;
+
+        let mut cpu = CPU::new();
+        cpu.load_rom(&ines(&prg_rom)).unwrap();
+        assert_eq!(cpu.mem_read(0x8000), 0x42);
+        assert_eq!(cpu.mem_read(0xc000), 0x43);
+
+        cpu.reset();
+        assert_eq!(cpu.program_counter, 0xc000);
+    }
+
+    #[test]
+    fn test_load_rom_bad_magic() {
+        let mut data = ines(&[0; 0x4000]);
+        data[0] = b'X';
+
+        let mut cpu = CPU::new();
+        assert_eq!(cpu.load_rom(&data), Err(RomError::InvalidMagic));
+    }
+
+    #[cfg(feature = "std")]
+    fn temp_file(name: &str, data: &[u8]) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("cubize-{}-{}", std::process::id(), name));
+        std::fs::write(&path, data).unwrap();
+        path
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_load_file() {
+        let path = temp_file("program.bin", &[0xa9, 0x05, 0xaa, 0x00]);
+
+        let mut cpu = CPU::new();
+        cpu.load_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        cpu.reset();
+        cpu.run();
+        assert_eq!(cpu.register_x, 0x05);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_load_file_ines() {
+        let mut prg_rom = vec![0; 0x4000];
+        prg_rom[0x0000] = 0x42;
+        let path = temp_file("program.nes", &ines(&prg_rom));
+
+        let mut cpu = CPU::new();
+        cpu.load_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(cpu.mem_read(0xc000), 0x42);
+
+        let err = cpu.load_file(&path).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn test_nmi() {
+        let mut cpu = CPU::new();
+        cpu.load(vec![0xa2, 0x01, 0xe8, 0x00, 0xa0, 0x42, 0x40])
+            .unwrap();
+        cpu.mem_write_u16(0xfffa, 0x8004);
+        cpu.reset();
+        cpu.step();
+        cpu.set_flag(CARRY, true);
+
+        cpu.trigger_nmi();
+        let result = cpu.step();
+        assert_eq!(result.cycles, 7);
+        assert_eq!(cpu.program_counter, 0x8004);
+        assert_eq!(cpu.register_sp, 0xfa);
+        assert_eq!(cpu.mem_read_u16(0x01fc), 0x8002);
+        assert_eq!(cpu.mem_read(0x01fb), CARRY | UNUSED | INTERRUPT_DISABLE);
+        assert!(cpu.get_flag(INTERRUPT_DISABLE));
+
+        cpu.run();
+        assert_eq!(cpu.register_x, 0x02);
+        assert_eq!(cpu.register_y, 0x42);
+    }
+
+    #[test]
+    fn test_irq() {
+        let mut cpu = CPU::new();
+        cpu.load(vec![0x58, 0xe8, 0x00, 0x00, 0xa0, 0x42, 0x40])
+            .unwrap();
+        cpu.mem_write_u16(0xfffe, 0x8004);
+        cpu.reset();
+        cpu.step();
+
+        cpu.trigger_irq();
+        cpu.step();
+        assert_eq!(cpu.program_counter, 0x8004);
+        assert_eq!(cpu.mem_read_u16(0x01fc), 0x8001);
+        assert_eq!(cpu.mem_read(0x01fb), UNUSED);
+        assert!(cpu.get_flag(INTERRUPT_DISABLE));
+
+        cpu.run();
+        assert_eq!(cpu.register_x, 0x01);
+        assert_eq!(cpu.register_y, 0x42);
+    }
+
+    #[test]
+    fn test_irq_ignored_when_disabled() {
+        let mut cpu = CPU::new();
+        cpu.load(vec![0x78, 0xe8, 0x58, 0xe8, 0x00]).unwrap();
+        cpu.mem_write_u16(0xfffe, 0x9000);
+        cpu.reset();
+        cpu.step();
+
+        cpu.trigger_irq();
+        cpu.step();
+        assert_eq!(cpu.program_counter, 0x8002);
+        assert_eq!(cpu.register_x, 0x01);
+
+        cpu.step();
+        cpu.step();
+        assert_eq!(cpu.program_counter, 0x8004);
+        assert_eq!(cpu.register_x, 0x02);
+    }
+
+    #[test]
+    fn test_run_stops_at_break() {
+        let mut cpu = CPU::new();
+        assert_eq!(cpu.load_and_run(vec![0xe8, 0x00]), StopReason::Break);
+        assert_eq!(cpu.register_x, 0x01);
+    }
+
+    #[test]
+    fn test_run_halts_at_end_of_memory() {
+        let mut cpu = CPU::new();
+        cpu.mem_write(0xfffe, 0xe8);
+        cpu.mem_write(0xffff, 0xe8);
+        cpu.mem_write_u16(0xfffc, 0xfffe);
+        cpu.reset();
+        assert_eq!(cpu.run(), StopReason::Halt);
+        assert_eq!(cpu.register_x, 0x02);
+        assert_eq!(cpu.program_counter, 0x0000);
+    }
+
+    #[test]
+    fn test_operand_past_end_of_memory_halts() {
+        let mut cpu = CPU::new();
+        cpu.mem_write(0xffff, 0xa9);
+        cpu.program_counter = 0xffff;
+        assert_eq!(cpu.step().stop, Some(StopReason::Halt));
+        assert_eq!(cpu.program_counter, 0xffff);
+    }
+
+    #[test]
+    fn test_jmp_at_end_of_memory() {
+        let mut cpu = CPU::new();
+        cpu.mem_write(0xfffd, 0x4c);
+        cpu.mem_write_u16(0xfffe, 0x9000);
+        cpu.program_counter = 0xfffd;
+        assert_eq!(cpu.step().stop, None);
+        assert_eq!(cpu.program_counter, 0x9000);
+    }
+
+    #[test]
+    fn test_run_stops_at_unknown_opcode() {
+        let mut cpu = CPU::new();
+        assert_eq!(
+            cpu.load_and_run(vec![0xe8, 0x02, 0xe8, 0x00]),
+            StopReason::UnknownOpcode(0x02)
+        );
+        assert_eq!(cpu.register_x, 0x01);
+        assert_eq!(cpu.program_counter, 0x8001);
+    }
+
+    #[test]
+    fn test_load_at() {
+        let mut cpu = CPU::new();
+        cpu.load_at(vec![0xe8, 0x00], 0x0600).unwrap();
+        cpu.reset();
+        assert_eq!(cpu.program_counter, 0x0600);
+
+        cpu.run();
+        assert_eq!(cpu.register_x, 0x01);
+        assert_eq!(brk_address(&cpu), 0x0601);
+    }
+
+    #[test]
+    fn test_load_too_large() {
+        let mut cpu = CPU::new();
+        let err = cpu.load(vec![0xea; 0x8001]).unwrap_err();
+        assert_eq!(
+            err,
+            LoadError::TooLarge {
+                addr: 0x8000,
+                len: 0x8001
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "program of 32769 bytes does not fit at 0x8000: only 32768 bytes available"
+        );
+
+        assert!(cpu.load(vec![0xea; 0x8000]).is_ok());
+    }
+
+    #[test]
+    fn test_reset_registers() {
+        let mut cpu = CPU::new();
+        cpu.load(vec![0x00]).unwrap();
+        cpu.register_a = 0x11;
+        cpu.register_x = 0x22;
+        cpu.register_y = 0x33;
+        cpu.register_sp = 0x44;
+        cpu.status = CARRY | DECIMAL | SIGN;
+        cpu.reset();
+
+        assert_eq!(cpu.register_a, 0);
+        assert_eq!(cpu.register_x, 0);
+        assert_eq!(cpu.register_y, 0);
+        assert_eq!(cpu.register_sp, 0xfd);
+        assert_eq!(cpu.status, INTERRUPT_DISABLE);
+        assert_eq!(cpu.program_counter, 0x8000);
+    }
+
+    #[test]
+    fn test_reset_preserves_memory() {
+        let mut cpu = CPU::new();
+        cpu.mem_write(0x0200, 0x42);
+
+        cpu.reset();
+        assert_eq!(cpu.mem_read(0x0200), 0x42);
+
+        cpu.reset_full();
+        assert_eq!(cpu.mem_read(0x0200), 0x00);
+        assert_eq!(cpu.program_counter, 0x0000);
+    }
+
+    #[test]
+    fn test_vectors() {
+        let mut cpu = CPU::new();
+        cpu.set_nmi_vector(0x1234);
+        cpu.set_reset_vector(0x8000);
+        cpu.set_irq_vector(0xc0de);
+
+        assert_eq!(cpu.nmi_vector(), 0x1234);
+        assert_eq!(cpu.reset_vector(), 0x8000);
+        assert_eq!(cpu.irq_vector(), 0xc0de);
+        assert_eq!(
+            cpu.dump(0xfffa, 6),
+            vec![0x34, 0x12, 0x00, 0x80, 0xde, 0xc0]
+        );
+    }
+
+    #[test]
+    fn test_stack_base() {
+        let mut cpu = CPU::new();
+        cpu.stack_base = 0x0300;
+        // PHA of 0x42, then PLA back into A after clearing it.
+        cpu.load(vec![0xa9, 0x42, 0x48, 0xa9, 0x00, 0x68]).unwrap();
+        cpu.reset();
+
+        cpu.step();
+        cpu.step();
+        assert_eq!(cpu.mem_read(0x03fd), 0x42);
+        assert_eq!(cpu.mem_read(0x01fd), 0x00);
+
+        cpu.step();
+        cpu.step();
+        assert_eq!(cpu.register_a, 0x42);
+        assert_eq!(cpu.register_sp, 0xfd);
+    }
+
+    #[test]
+    fn test_status_string() {
+        let mut cpu = CPU::new();
+        assert_eq!(cpu.status_string(), "nv-bdizc");
+
+        cpu.status = CARRY | SIGN;
+        assert_eq!(cpu.status_string(), "Nv-bdizC");
+
+        cpu.status = 0xff;
+        assert_eq!(cpu.status_string(), "NV-BDIZC");
+    }
+
+    #[test]
+    fn test_signed_registers() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![0xa9, 0xff, 0xa2, 0x80, 0xa0, 0x7f, 0x00]);
+        assert_eq!(cpu.register_a_signed(), -1);
+        assert_eq!(cpu.register_x_signed(), -128);
+        assert_eq!(cpu.register_y_signed(), 127);
+    }
+
+    #[test]
+    fn test_flags_round_trip() {
+        let mut cpu = CPU::new();
+        cpu.status = CARRY | UNUSED | OVERFLOW | SIGN;
+
+        let flags = cpu.flags();
+        assert!(flags.carry && flags.overflow && flags.negative);
+        assert!(!flags.zero && !flags.interrupt_disable && !flags.decimal);
+        assert!(!flags.break_command);
+
+        cpu.set_flags(flags);
+        assert_eq!(cpu.status, CARRY | UNUSED | OVERFLOW | SIGN);
+
+        for status in 0..=0xff {
+            assert_eq!(u8::from(StatusFlags::from(status)), status | UNUSED);
+        }
+    }
+
+    #[test]
+    fn test_set_flags_sets_unused() {
+        let mut cpu = CPU::new();
+        cpu.set_flags(StatusFlags {
+            zero: true,
+            ..StatusFlags::default()
+        });
+        assert_eq!(cpu.status, ZERO | UNUSED);
+    }
+
+    #[test]
+    fn test_disassemble() {
+        let mut cpu = CPU::new();
+        cpu.load(vec![
+            0xa9, 0x05, 0x85, 0x10, 0x9d, 0x00, 0x02, 0x0a, 0xb1, 0x20, 0xd0, 0xf4, 0x6c, 0x34,
+            0x12, 0xe8, 0x00,
+        ])
+        .unwrap();
+
+        assert_eq!(
+            cpu.disassemble(0x8000, 0x8011),
+            vec![
+                (0x8000, "LDA #$05".to_string()),
+                (0x8002, "STA $10".to_string()),
+                (0x8004, "STA $0200,X".to_string()),
+                (0x8007, "ASL A".to_string()),
+                (0x8008, "LDA ($20),Y".to_string()),
+                (0x800a, "BNE $8000".to_string()),
+                (0x800c, "JMP ($1234)".to_string()),
+                (0x800f, "INX".to_string()),
+                (0x8010, "BRK".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_peek_instruction() {
+        let mut cpu = CPU::new();
+        cpu.load(vec![0xbd, 0x34, 0x12, 0x02]).unwrap();
+        cpu.reset();
+
+        assert_eq!(
+            cpu.peek_instruction(),
+            Some(DecodedInstruction {
+                mnemonic: Mnemonic::LDA,
+                addr_mode: AddressingMode::AbsoluteX,
+                operand: vec![0x34, 0x12],
+                text: "LDA $1234,X".to_string(),
+            })
+        );
+        assert_eq!(cpu.program_counter, 0x8000);
+        assert_eq!(cpu.total_cycles(), 0);
+
+        cpu.step();
+        assert_eq!(cpu.peek_instruction(), None);
+    }
+
+    #[test]
+    fn test_disassemble_invalid_bytes() {
+        let mut cpu = CPU::new();
+        cpu.load(vec![0x02, 0xe8, 0xad, 0x00]).unwrap();
+
+        assert_eq!(
+            cpu.disassemble(0x8000, 0x8004),
+            vec![
+                (0x8000, ".byte $02".to_string()),
+                (0x8001, "INX".to_string()),
+                (0x8002, ".byte $AD".to_string()),
+                (0x8003, "BRK".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_trace_nestest() {
+        let mut cpu = CPU::new();
+        cpu.mem_write(0xc000, 0x4c);
+        cpu.mem_write_u16(0xc001, 0xc5f5);
+        cpu.load_at(vec![0xa2, 0x00, 0x86, 0x00, 0x86, 0x10, 0x86, 0x11], 0xc5f5)
+            .unwrap();
+        cpu.program_counter = 0xc000;
+        cpu.status = 0x24;
+        cpu.cycles = 7;
+
+        let expected = [
+            "C000  4C F5 C5  JMP $C5F5                       A:00 X:00 Y:00 P:24 SP:FD CYC:7",
+            "C5F5  A2 00     LDX #$00                        A:00 X:00 Y:00 P:24 SP:FD CYC:10",
+            "C5F7  86 00     STX $00 = 00                    A:00 X:00 Y:00 P:26 SP:FD CYC:12",
+            "C5F9  86 10     STX $10 = 00                    A:00 X:00 Y:00 P:26 SP:FD CYC:15",
+            "C5FB  86 11     STX $11 = 00                    A:00 X:00 Y:00 P:26 SP:FD CYC:18",
+        ];
+        for line in expected.iter() {
+            assert_eq!(cpu.trace(), *line);
+            cpu.step();
+        }
+    }
+
+    #[test]
+    fn test_trace_operand_values() {
+        let mut cpu = CPU::new();
+        cpu.register_x = 0x02;
+        cpu.register_y = 0x10;
+        cpu.mem_write_u16(0x0082, 0x0200);
+        cpu.mem_write_u16(0x0089, 0x02f0);
+        cpu.mem_write(0x0200, 0x5a);
+        cpu.mem_write(0x0300, 0x89);
+
+        let cases: [(&[u8], &str); 4] = [
+            (&[0xa1, 0x80], "A1 80     LDA ($80,X) @ 82 = 0200 = 5A"),
+            (&[0xb1, 0x89], "B1 89     LDA ($89),Y = 02F0 @ 0300 = 89"),
+            (&[0xbd, 0xfe, 0x01], "BD FE 01  LDA $01FE,X @ 0200 = 5A"),
+            (&[0x04, 0x00], "04 00    *NOP $00 = 00"),
+        ];
+        for (program, line) in cases.iter() {
+            cpu.load_at(program.to_vec(), 0x0600).unwrap();
+            cpu.program_counter = 0x0600;
+            assert_eq!(&cpu.trace()[6..6 + line.len()], *line);
+        }
+    }
+
+    #[test]
+    fn test_trace_log() {
+        let mut cpu = CPU::new();
+        cpu.load(vec![0xa9, 0x05, 0xaa, 0xe8, 0x02]).unwrap();
+        cpu.reset();
+        cpu.enable_trace(8);
+
+        assert_eq!(cpu.run(), StopReason::UnknownOpcode(0x02));
+        let log = cpu.trace_log();
+        let executed: Vec<(u16, u8, &str)> = log
+            .iter()
+            .map(|entry| (entry.addr, entry.opcode, entry.disassembly.as_str()))
+            .collect();
+        assert_eq!(
+            executed,
+            vec![
+                (0x8000, 0xa9, "LDA #$05"),
+                (0x8002, 0xaa, "TAX"),
+                (0x8003, 0xe8, "INX"),
+            ]
+        );
+        assert_eq!(log[1].register_a, 0x05);
+        assert_eq!(log[1].register_x, 0x00);
+        assert_eq!(log[2].register_x, 0x05);
+        assert_eq!(log[2].register_sp, 0xfd);
+    }
+
+    #[test]
+    fn test_trace_log_keeps_most_recent() {
+        let mut cpu = CPU::new();
+        cpu.load(vec![0xe8; 10]).unwrap();
+        cpu.reset();
+        cpu.enable_trace(3);
+
+        for _ in 0..10 {
+            cpu.step();
+        }
+        let addrs: Vec<u16> = cpu.trace_log().iter().map(|entry| entry.addr).collect();
+        assert_eq!(addrs, vec![0x8007, 0x8008, 0x8009]);
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_save_and_load_state() {
+        let mut cpu = CPU::new();
+        cpu.load(vec![0xe8, 0x86, 0x10, 0xe8, 0x86, 0x11, 0x00])
+            .unwrap();
+        cpu.reset();
+        cpu.step();
+        cpu.step();
+        let snapshot = cpu.save_state();
+
+        cpu.run();
+        assert_eq!(cpu.register_x, 0x02);
+        assert_eq!(cpu.mem_read(0x11), 0x02);
+
+        cpu.load_state(&snapshot).unwrap();
+        assert_eq!(cpu.save_state(), snapshot);
+        assert_eq!(cpu.register_x, 0x01);
+        assert_eq!(cpu.program_counter, 0x8003);
+        assert_eq!(cpu.total_cycles(), 5);
+        assert_eq!(cpu.mem_read(0x10), 0x01);
+        assert_eq!(cpu.mem_read(0x11), 0x00);
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_load_state_keeps_trace() {
+        let mut cpu = CPU::new();
+        cpu.load(vec![0xe8, 0xe8, 0x00]).unwrap();
+        cpu.reset();
+        cpu.enable_trace(4);
+        cpu.step();
+
+        let state = cpu.save_state();
+        cpu.load_state(&state).unwrap();
+        cpu.step();
+        let addrs: Vec<u16> = cpu.trace_log().iter().map(|entry| entry.addr).collect();
+        assert_eq!(addrs, vec![0x8000, 0x8001]);
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_load_state_rejects_garbage() {
+        let writes = Rc::new(RefCell::new(0));
+        let mut cpu = CPU::new();
+        let sink = Rc::clone(&writes);
+        cpu.on_write(0x0010, move |_, _| *sink.borrow_mut() += 1);
+        assert!(cpu.load_state(&[0x01, 0x02]).is_err());
+
+        // Hooks survive the failed load.
+        cpu.load_and_run(vec![0x85, 0x10, 0x00]);
+        assert_eq!(*writes.borrow(), 1);
+    }
+
+    #[derive(Debug, PartialEq, Eq)]
+    enum Access {
+        Read(u16, u8),
+        Write(u16, u8),
+    }
+
+    struct RecordingBus {
+        memory: FlatMemory,
+        accesses: Vec<Access>,
+    }
+
+    impl Bus for RecordingBus {
+        fn read(&mut self, addr: u16) -> u8 {
+            let data = self.memory.read(addr);
+            self.accesses.push(Access::Read(addr, data));
+            data
+        }
+
+        fn write(&mut self, addr: u16, data: u8) {
+            self.accesses.push(Access::Write(addr, data));
+            self.memory.write(addr, data);
+        }
+
+        fn peek(&self, addr: u16) -> u8 {
+            self.memory.peek(addr)
+        }
+
+        fn poke(&mut self, addr: u16, data: u8) {
+            self.memory.poke(addr, data);
+        }
+    }
+
+    #[test]
+    fn test_custom_bus() {
+        let mut cpu = CPU::with_bus(RecordingBus {
+            memory: FlatMemory::new(),
+            accesses: Vec::new(),
+        });
+        cpu.load(vec![0xa5, 0x10, 0x8d, 0x00, 0x02, 0x00]).unwrap();
+        cpu.mem_write(0x10, 0x42);
+        cpu.reset();
+        cpu.bus.accesses.clear();
+
+        cpu.step();
+        cpu.step();
+        assert_eq!(
+            cpu.bus.accesses,
+            vec![
+                Access::Read(0x8000, 0xa5),
+                Access::Read(0x0010, 0x42),
+                Access::Read(0x8002, 0x8d),
+                Access::Write(0x0200, 0x42),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rmw_dummy_write() {
+        let mut cpu = CPU::with_bus(RecordingBus {
+            memory: FlatMemory::new(),
+            accesses: Vec::new(),
+        });
+        cpu.load(vec![0xee, 0x00, 0x40, 0x00]).unwrap();
+        cpu.mem_write(0x4000, 0x41);
+        cpu.reset();
+        cpu.bus.accesses.clear();
+
+        cpu.step();
+        assert_eq!(
+            cpu.bus.accesses,
+            vec![
+                Access::Read(0x8000, 0xee),
+                Access::Read(0x4000, 0x41),
+                Access::Write(0x4000, 0x41),
+                Access::Write(0x4000, 0x42),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_accumulator_mode_skips_bus() {
+        let mut cpu = CPU::with_bus(RecordingBus {
+            memory: FlatMemory::new(),
+            accesses: Vec::new(),
+        });
+        cpu.load(vec![0x0a, 0x4a, 0x2a, 0x6a, 0x00]).unwrap();
+        cpu.reset();
+        cpu.register_a = 0x81;
+        cpu.bus.accesses.clear();
+
+        cpu.step();
+        assert_eq!(cpu.register_a, 0x02);
+        assert!(cpu.get_flag(CARRY));
+        for _ in 0..3 {
+            cpu.step();
+        }
+        assert_eq!(
+            cpu.bus.accesses,
+            vec![
+                Access::Read(0x8000, 0x0a),
+                Access::Read(0x8001, 0x4a),
+                Access::Read(0x8002, 0x2a),
+                Access::Read(0x8003, 0x6a),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_on_write() {
+        let output = Rc::new(RefCell::new(Vec::new()));
+        let mut cpu = CPU::new();
+        let sink = Rc::clone(&output);
+        cpu.on_write(0x00ff, move |addr, data| {
+            sink.borrow_mut().push((addr, data))
+        });
+
+        cpu.load_and_run(vec![
+            0xa9, 0x48, 0x85, 0xff, 0xa9, 0x69, 0x8d, 0xff, 0x00, 0x85, 0xfe, 0x00,
+        ]);
+        assert_eq!(*output.borrow(), vec![(0x00ff, b'H'), (0x00ff, b'i')]);
+        assert_eq!(cpu.mem_read(0x00ff), b'i');
+
+        cpu.mem_write(0x00ff, 0x00);
+        assert_eq!(output.borrow().len(), 2);
+    }
+
+    #[test]
+    fn test_on_read() {
+        let mut cpu = CPU::new();
+        let mut next = 0x10;
+        cpu.on_read(0x00fe, move |_| {
+            next += 1;
+            next
+        });
+
+        cpu.load_and_run(vec![0xa5, 0xfe, 0xa6, 0xfe, 0x00]);
+        assert_eq!(cpu.register_a, 0x11);
+        assert_eq!(cpu.register_x, 0x12);
+        assert_eq!(cpu.mem_read(0x00fe), 0x00);
+    }
+
+    #[test]
+    fn test_0xa7_lax_zero_page() {
+        let mut cpu = CPU::new();
+        cpu.mem_write(0x10, 0x80);
+        cpu.load_and_run(vec![0xa7, 0x10, 0x00]);
+        assert_eq!(cpu.register_a, 0x80);
+        assert_eq!(cpu.register_x, 0x80);
+        assert!(cpu.get_flag(SIGN));
+        assert!(!cpu.get_flag(ZERO));
+    }
+
+    #[test]
+    fn test_0xbf_lax_absolute_y_page_cross() {
+        let mut cpu = CPU::new();
+        cpu.mem_write(0x0300, 0x00);
+        cpu.load(vec![0xbf, 0xff, 0x02, 0x00]).unwrap();
+        cpu.reset();
+        cpu.register_a = 0x42;
+        cpu.register_y = 0x01;
+        assert_eq!(cpu.step().cycles, 5);
+        assert_eq!(cpu.register_a, 0x00);
+        assert_eq!(cpu.register_x, 0x00);
+        assert!(cpu.get_flag(ZERO));
+    }
+
+    #[test]
+    fn test_0x87_sax_zero_page() {
+        let mut cpu = CPU::new();
+        cpu.load(vec![0x87, 0x10, 0x00]).unwrap();
+        cpu.reset();
+        cpu.register_a = 0b1100_1100;
+        cpu.register_x = 0b1010_1010;
+        cpu.status = SIGN;
+        cpu.step();
+        assert_eq!(cpu.mem_read(0x10), 0b1000_1000);
+        assert_eq!(cpu.status, SIGN);
+    }
+
+    #[test]
+    fn test_0x97_sax_zero_page_y() {
+        let mut cpu = CPU::new();
+        cpu.load(vec![0x97, 0xf0, 0x00]).unwrap();
+        cpu.reset();
+        cpu.register_a = 0x0f;
+        cpu.register_x = 0x33;
+        cpu.register_y = 0x20;
+        cpu.step();
+        assert_eq!(cpu.mem_read(0x10), 0x03);
+    }
+
+    #[test]
+    fn test_0xc7_dcp_zero_page() {
+        let mut cpu = CPU::new();
+        cpu.mem_write(0x10, 0x43);
+        cpu.load_and_run(vec![0xa9, 0x42, 0xc7, 0x10, 0x00]);
+        assert_eq!(cpu.mem_read(0x10), 0x42);
+        assert_eq!(cpu.register_a, 0x42);
+        assert!(cpu.get_flag(ZERO));
+        assert!(cpu.get_flag(CARRY));
+    }
+
+    #[test]
+    fn test_0xdf_dcp_absolute_x_fixed_cycles() {
+        let mut cpu = CPU::new();
+        cpu.mem_write(0x0300, 0x00);
+        cpu.load(vec![0xdf, 0xff, 0x02, 0x00]).unwrap();
+        cpu.reset();
+        cpu.register_a = 0x01;
+        cpu.register_x = 0x01;
+        assert_eq!(cpu.step().cycles, 7);
+        assert_eq!(cpu.mem_read(0x0300), 0xff);
+        assert!(!cpu.get_flag(CARRY));
+        assert!(!cpu.get_flag(ZERO));
+        assert!(!cpu.get_flag(SIGN));
+    }
+
+    #[test]
+    fn test_0xe7_isc_zero_page() {
+        let mut cpu = CPU::new();
+        cpu.mem_write(0x10, 0x0f);
+        cpu.load_and_run(vec![0xa9, 0x50, 0x38, 0xe7, 0x10, 0x00]);
+        assert_eq!(cpu.mem_read(0x10), 0x10);
+        assert_eq!(cpu.register_a, 0x40);
+        assert!(cpu.get_flag(CARRY));
+        assert!(!cpu.get_flag(ZERO));
+        assert!(!cpu.get_flag(OVERFLOW));
+        assert!(!cpu.get_flag(SIGN));
+    }
+
+    #[test]
+    fn test_0xef_isc_absolute_borrow_and_overflow() {
         let mut cpu = CPU::new();
-        cpu.load_and_run(vec![0xa9, 0x7f, 0x69, 0x01, 0x00]);
+        cpu.mem_write(0x0200, 0xfe);
+        cpu.load_and_run(vec![0xa9, 0x7f, 0x38, 0xef, 0x00, 0x02, 0x00]);
+        assert_eq!(cpu.mem_read(0x0200), 0xff);
         assert_eq!(cpu.register_a, 0x80);
         assert!(!cpu.get_flag(CARRY));
-        assert!(!cpu.get_flag(ZERO));
         assert!(cpu.get_flag(OVERFLOW));
         assert!(cpu.get_flag(SIGN));
     }
 
     #[test]
-    fn test_adc_carry() {
+    fn test_0x07_slo_zero_page() {
         let mut cpu = CPU::new();
-        cpu.load_and_run(vec![0xa9, 0xff, 0x69, 0x01, 0x00]);
-        assert_eq!(cpu.register_a, 0x00);
+        cpu.mem_write(0x10, 0b1010_0001);
+        cpu.load_and_run(vec![0xa9, 0b0000_0100, 0x07, 0x10, 0x00]);
+        assert_eq!(cpu.mem_read(0x10), 0b0100_0010);
+        assert_eq!(cpu.register_a, 0b0100_0110);
         assert!(cpu.get_flag(CARRY));
-        assert!(cpu.get_flag(ZERO));
-        assert!(!cpu.get_flag(OVERFLOW));
+        assert!(!cpu.get_flag(ZERO));
         assert!(!cpu.get_flag(SIGN));
     }
 
     #[test]
-    fn test_sbc() {
+    fn test_0x0f_slo_absolute_sign() {
         let mut cpu = CPU::new();
-        cpu.load_and_run(vec![0xa9, 0x10, 0xe9, 0x0f, 0x00]);
-        assert_eq!(cpu.register_a, 0x00);
+        cpu.mem_write(0x0200, 0b0100_0000);
+        cpu.load_and_run(vec![0xa9, 0x01, 0x0f, 0x00, 0x02, 0x00]);
+        assert_eq!(cpu.mem_read(0x0200), 0b1000_0000);
+        assert_eq!(cpu.register_a, 0b1000_0001);
         assert!(!cpu.get_flag(CARRY));
+        assert!(cpu.get_flag(SIGN));
+    }
+
+    #[test]
+    fn test_0x27_rla_zero_page() {
+        let mut cpu = CPU::new();
+        cpu.mem_write(0x10, 0b1100_0001);
+        cpu.load_and_run(vec![0xa9, 0b1000_0111, 0x38, 0x27, 0x10, 0x00]);
+        assert_eq!(cpu.mem_read(0x10), 0b1000_0011);
+        assert_eq!(cpu.register_a, 0b1000_0011);
+        assert!(cpu.get_flag(CARRY));
+        assert!(cpu.get_flag(SIGN));
         assert!(!cpu.get_flag(ZERO));
+    }
+
+    #[test]
+    fn test_0x47_sre_zero_page() {
+        let mut cpu = CPU::new();
+        cpu.mem_write(0x10, 0b0000_0011);
+        cpu.load_and_run(vec![0xa9, 0b0000_0001, 0x47, 0x10, 0x00]);
+        assert_eq!(cpu.mem_read(0x10), 0b0000_0001);
+        assert_eq!(cpu.register_a, 0x00);
+        assert!(cpu.get_flag(CARRY));
+        assert!(cpu.get_flag(ZERO));
+    }
+
+    #[test]
+    fn test_0x67_rra_zero_page() {
+        let mut cpu = CPU::new();
+        cpu.mem_write(0x10, 0x21);
+        cpu.load_and_run(vec![0xa9, 0x10, 0x18, 0x67, 0x10, 0x00]);
+        assert_eq!(cpu.mem_read(0x10), 0x10);
+        // The carry shifted out of memory feeds the addition.
+        assert_eq!(cpu.register_a, 0x21);
+        assert!(!cpu.get_flag(CARRY));
         assert!(!cpu.get_flag(OVERFLOW));
-        assert!(!cpu.get_flag(SIGN));
+    }
+
+    #[test]
+    fn test_0x6f_rra_absolute_overflow() {
+        let mut cpu = CPU::new();
+        cpu.mem_write(0x0200, 0x80);
+        cpu.load_and_run(vec![0xa9, 0x40, 0x18, 0x6f, 0x00, 0x02, 0x00]);
+        assert_eq!(cpu.mem_read(0x0200), 0x40);
+        assert_eq!(cpu.register_a, 0x80);
+        assert!(cpu.get_flag(OVERFLOW));
+        assert!(cpu.get_flag(SIGN));
+        assert!(!cpu.get_flag(CARRY));
+    }
+
+    #[test]
+    fn test_run_for_exhausts_budget() {
+        let mut cpu = CPU::new();
+        cpu.load(vec![0xa2, 0x01, 0xd0, 0xfe, 0x00]).unwrap();
+        cpu.reset();
+
+        assert_eq!(cpu.run_for(100), StopReason::CyclesExhausted);
+        assert_eq!(cpu.program_counter, 0x8002);
+        assert!(cpu.total_cycles() >= 100);
+        assert!(cpu.total_cycles() < 103);
+
+        assert_eq!(cpu.run_for(30), StopReason::CyclesExhausted);
+        assert!(cpu.total_cycles() >= 130);
+    }
+
+    #[test]
+    fn test_run_bytes_never_panics() {
+        let mut seed: u32 = 0x1234_5678;
+        let mut next = || {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            (seed >> 16) as u8
+        };
+
+        for len in 0..2000 {
+            let program: Vec<u8> = (0..len % 97).map(|_| next()).collect();
+            CPU::new().run_bytes(&program, 10_000);
+        }
+
+        let program: Vec<u8> = (0..0x9000).map(|_| next()).collect();
+        CPU::new().run_bytes(&program, 100_000);
+        assert_eq!(
+            CPU::new().run_bytes(&[0x02], usize::MAX),
+            StopReason::UnknownOpcode(0x02)
+        );
+    }
+
+    #[test]
+    fn test_run_for_stops_at_break() {
+        let mut cpu = CPU::new();
+        cpu.load(vec![0xe8, 0x00]).unwrap();
+        cpu.reset();
+
+        assert_eq!(cpu.run_for(1000), StopReason::Break);
+        assert_eq!(cpu.register_x, 0x01);
+        assert_eq!(cpu.total_cycles(), 9);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_run_realtime() {
+        let mut cpu = CPU::new();
+        // LDX #$00; DEX; BNE -3; BRK, about 1300 cycles.
+        cpu.load(vec![0xa2, 0x00, 0xca, 0xd0, 0xfd, 0x00]).unwrap();
+        cpu.reset();
+
+        let start = Instant::now();
+        assert_eq!(cpu.run_realtime(20_000), StopReason::Break);
+        let elapsed = start.elapsed();
+
+        let expected = Duration::from_micros(cpu.total_cycles() as u64 * 50);
+        assert!(
+            elapsed + Duration::from_millis(2) >= expected,
+            "{:?}",
+            elapsed
+        );
+        assert!(
+            elapsed < expected + Duration::from_millis(500),
+            "{:?}",
+            elapsed
+        );
+    }
+
+    #[test]
+    fn test_tick() {
+        let mut cpu = CPU::new();
+        cpu.mem_write(0x1234, 0x42);
+        cpu.load(vec![0xad, 0x34, 0x12, 0x00]).unwrap();
+        cpu.reset();
+
+        for _ in 0..3 {
+            assert_eq!(cpu.tick(), None);
+            assert_eq!(cpu.register_a, 0x00);
+            assert_eq!(cpu.program_counter, 0x8000);
+        }
+        assert_eq!(cpu.tick(), None);
+        assert_eq!(cpu.register_a, 0x42);
+        assert_eq!(cpu.program_counter, 0x8003);
+        assert_eq!(cpu.total_cycles(), 4);
+
+        for _ in 0..6 {
+            assert_eq!(cpu.tick(), None);
+        }
+        assert_eq!(cpu.tick(), Some(StopReason::Break));
+    }
+
+    #[test]
+    fn test_tick_across_breakpoint() {
+        let mut cpu = CPU::new();
+        cpu.mem_write(0x1234, 0x42);
+        cpu.load(vec![0xad, 0x34, 0x12, 0xe8]).unwrap();
+        cpu.reset();
+        cpu.add_breakpoint(0x8000);
+
+        for _ in 0..3 {
+            assert_eq!(cpu.tick(), None);
+        }
+        assert_eq!(cpu.tick(), Some(StopReason::Breakpoint(0x8000)));
+        assert_eq!(cpu.register_a, 0x00);
+
+        // Resuming completes the LDA on the next tick, then INX takes two.
+        assert_eq!(cpu.tick(), None);
+        assert_eq!(cpu.register_a, 0x42);
+        assert_eq!(cpu.total_cycles(), 4);
+        cpu.tick();
+        assert_eq!(cpu.register_x, 0x00);
+        cpu.tick();
+        assert_eq!(cpu.register_x, 0x01);
+        assert_eq!(cpu.total_cycles(), 6);
+    }
+
+    #[test]
+    fn test_reset_mid_tick() {
+        let mut cpu = CPU::new();
+        cpu.load(vec![0xad, 0x34, 0x12]).unwrap();
+        cpu.reset();
+        for _ in 0..3 {
+            cpu.tick();
+        }
+
+        cpu.load(vec![0xe8]).unwrap();
+        cpu.reset();
+        cpu.tick();
+        assert_eq!(cpu.register_x, 0x00);
+        cpu.tick();
+        assert_eq!(cpu.register_x, 0x01);
+    }
+
+    #[test]
+    fn test_tick_page_cross_penalty() {
+        let mut cpu = CPU::new();
+        // LDX #$10; LDA $12F0,X; INX
+        cpu.load(vec![0xa2, 0x10, 0xbd, 0xf0, 0x12, 0xe8]).unwrap();
+        cpu.mem_write(0x1300, 0x42);
+        cpu.reset();
+
+        let mut ticks = 0;
+        while cpu.register_a != 0x42 {
+            cpu.tick();
+            ticks += 1;
+        }
+        assert_eq!(ticks, 2 + 4);
+
+        // The penalty cycle passes before INX starts its two cycles.
+        cpu.tick();
+        cpu.tick();
+        assert_eq!(cpu.register_x, 0x10);
+        cpu.tick();
+        assert_eq!(cpu.register_x, 0x11);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_run_realtime_zero_hz_is_unthrottled() {
+        let mut cpu = CPU::new();
+        cpu.load(vec![0xa2, 0x00, 0xca, 0xd0, 0xfd, 0x00]).unwrap();
+        cpu.reset();
+
+        assert_eq!(cpu.run_realtime(0), StopReason::Break);
+        assert_eq!(cpu.register_x, 0x00);
+    }
+
+    #[test]
+    fn test_breakpoint() {
+        let mut cpu = CPU::new();
+        cpu.load(vec![0xe8, 0xe8, 0xe8, 0x00]).unwrap();
+        cpu.reset();
+        cpu.add_breakpoint(0x8002);
+
+        assert_eq!(cpu.run(), StopReason::Breakpoint(0x8002));
+        assert_eq!(cpu.program_counter, 0x8002);
+        assert_eq!(cpu.register_x, 0x02);
+
+        assert_eq!(cpu.run(), StopReason::Break);
+        assert_eq!(cpu.register_x, 0x03);
+    }
+
+    #[test]
+    fn test_breakpoint_after_reset() {
+        let mut cpu = CPU::new();
+        cpu.load(vec![0xe8, 0x00]).unwrap();
+        cpu.reset();
+        cpu.add_breakpoint(0x8000);
+
+        assert_eq!(cpu.run(), StopReason::Breakpoint(0x8000));
+        cpu.reset();
+        assert_eq!(cpu.run(), StopReason::Breakpoint(0x8000));
+        assert_eq!(cpu.register_x, 0x00);
+    }
+
+    #[test]
+    fn test_breakpoint_in_loop() {
+        let mut cpu = CPU::new();
+        cpu.load(vec![0xa2, 0x03, 0xca, 0xd0, 0xfd, 0x00]).unwrap();
+        cpu.reset();
+        cpu.add_breakpoint(0x8002);
+
+        assert_eq!(cpu.run(), StopReason::Breakpoint(0x8002));
+        assert_eq!(cpu.register_x, 0x03);
+        assert_eq!(cpu.run(), StopReason::Breakpoint(0x8002));
+        assert_eq!(cpu.register_x, 0x02);
+
+        cpu.remove_breakpoint(0x8002);
+        assert_eq!(cpu.run(), StopReason::Break);
+        assert_eq!(cpu.register_x, 0x00);
+    }
+
+    #[test]
+    fn test_watchpoint_write() {
+        let mut cpu = CPU::new();
+        cpu.load(vec![0xa5, 0x10, 0xa9, 0x42, 0x85, 0x10, 0xe8, 0x00])
+            .unwrap();
+        cpu.reset();
+        cpu.add_watchpoint(0x10, false, true);
+
+        assert_eq!(
+            cpu.run(),
+            StopReason::Watchpoint {
+                addr: 0x10,
+                write: true
+            }
+        );
+        assert_eq!(cpu.program_counter, 0x8006);
+        assert_eq!(cpu.mem_read(0x10), 0x42);
+
+        assert_eq!(cpu.run(), StopReason::Break);
+        assert_eq!(cpu.register_x, 0x01);
+    }
+
+    #[test]
+    fn test_watchpoint_read() {
+        let mut cpu = CPU::new();
+        cpu.load(vec![0x85, 0x10, 0xa6, 0x10, 0x00]).unwrap();
+        cpu.reset();
+        cpu.add_watchpoint(0x10, true, false);
+
+        assert_eq!(
+            cpu.run(),
+            StopReason::Watchpoint {
+                addr: 0x10,
+                write: false
+            }
+        );
+        assert_eq!(cpu.program_counter, 0x8004);
+
+        cpu.remove_watchpoint(0x10);
+        cpu.program_counter = 0x8000;
+        assert_eq!(cpu.run(), StopReason::Break);
+    }
+
+    #[test]
+    fn test_watchpoint_indirect_pointer() {
+        let mut cpu = CPU::new();
+        // LDA ($20),Y; LDA ($30,X); JMP ($0040)
+        cpu.load(vec![0xb1, 0x20, 0xa1, 0x30, 0x6c, 0x40, 0x00])
+            .unwrap();
+        cpu.mem_write_u16(0x40, 0x9000);
+        cpu.reset();
+        cpu.add_watchpoint(0x21, true, false);
+        cpu.add_watchpoint(0x30, true, false);
+        cpu.add_watchpoint(0x41, true, false);
+
+        let expected = [(0x21, 0x8002), (0x30, 0x8004), (0x41, 0x9000)];
+        for &(addr, pc) in expected.iter() {
+            assert_eq!(cpu.run(), StopReason::Watchpoint { addr, write: false });
+            assert_eq!(cpu.program_counter, pc);
+        }
+    }
+
+    #[test]
+    fn test_watchpoint_nmi_push() {
+        let mut cpu = CPU::new();
+        cpu.load(vec![0xe8, 0xe8, 0x00]).unwrap();
+        cpu.reset();
+        cpu.set_nmi_vector(0x8001);
+        cpu.add_watchpoint(0x01fd, false, true);
+
+        cpu.trigger_nmi();
+        let result = cpu.step();
+        assert_eq!(
+            result.stop,
+            Some(StopReason::Watchpoint {
+                addr: 0x01fd,
+                write: true
+            })
+        );
+        assert_eq!(cpu.program_counter, 0x8001);
+
+        // The hit is not reported again by the next instruction.
+        assert_eq!(cpu.step().stop, None);
+        assert_eq!(cpu.register_x, 0x01);
+    }
+
+    #[test]
+    fn test_watchpoint_in_brk_frame() {
+        let mut cpu = CPU::new();
+        cpu.load(vec![0x00]).unwrap();
+        cpu.reset();
+        cpu.set_irq_vector(0x9000);
+        cpu.mem_write(0x9000, 0xe8);
+        cpu.add_watchpoint(0x01fb, false, true);
+
+        assert_eq!(
+            cpu.step().stop,
+            Some(StopReason::Watchpoint {
+                addr: 0x01fb,
+                write: true
+            })
+        );
+        assert_eq!(cpu.program_counter, 0x9000);
+        assert_eq!(cpu.step().stop, None);
+    }
+
+    #[test]
+    fn test_mem_diff() {
+        let mut cpu = CPU::new();
+        // Stores X at $0200,X for X = 0..4.
+        cpu.load_and_run(vec![
+            0xa2, 0x00, 0x8a, 0x9d, 0x00, 0x02, 0xe8, 0xe0, 0x04, 0xd0, 0xf7, 0x00,
+        ]);
+        assert_eq!(cpu.mem_diff(0x0200, &[0, 1, 2, 3]), vec![]);
+        assert!(cpu.mem_eq(0x0200, &[0, 1, 2, 3]));
+
+        assert_eq!(
+            cpu.mem_diff(0x0200, &[0, 1, 5, 3, 9]),
+            vec![(0x0202, 2, 5), (0x0204, 0, 9)]
+        );
+        assert!(!cpu.mem_eq(0x0200, &[1]));
+    }
+
+    #[test]
+    fn test_dump() {
+        let mut cpu = CPU::new();
+        cpu.mem_write(0xffff, 0x01);
+        cpu.mem_write(0x0000, 0x02);
+        assert_eq!(cpu.dump(0xffff, 3), vec![0x01, 0x02, 0x00]);
+        assert_eq!(cpu.dump(0x0000, 0), vec![]);
+    }
+
+    #[test]
+    fn test_hexdump() {
+        let mut cpu = CPU::new();
+        for (i, &byte) in b"Hello, world!\n\x00\xffcubize".iter().enumerate() {
+            cpu.mem_write(0x0200 + i as u16, byte);
+        }
+
+        assert_eq!(
+            cpu.hexdump(0x0200, 22),
+            "0200  48 65 6c 6c 6f 2c 20 77  6f 72 6c 64 21 0a 00 ff  |Hello, world!...|\n\
+             0210  63 75 62 69 7a 65                                 |cubize|\n"
+        );
+    }
+
+    #[test]
+    fn test_default() {
+        let cpu = CPU::default();
+        let new = CPU::new();
+        assert_eq!(cpu.register_a, new.register_a);
+        assert_eq!(cpu.register_x, new.register_x);
+        assert_eq!(cpu.register_y, new.register_y);
+        assert_eq!(cpu.register_sp, new.register_sp);
+        assert_eq!(cpu.status, new.status);
+        assert_eq!(cpu.program_counter, new.program_counter);
+        assert_eq!(cpu.total_cycles(), 0);
+    }
+
+    #[test]
+    fn test_opcode_accessors() {
+        let op = CPU_OPCODES.iter().find(|op| op.opcode() == 0xa9).unwrap();
+        assert!(matches!(op.mnemonic(), Mnemonic::LDA));
+        assert_eq!(op.bytes(), 2);
+        assert_eq!(op.cycles(), 2);
+        assert!(matches!(op.addr_mode(), AddressingMode::Immediate));
+    }
+
+    #[test]
+    fn test_mnemonic_round_trip() {
+        for op in CPU_OPCODES {
+            let name = op.mnemonic().to_string();
+            let parsed: Mnemonic = name.parse().unwrap();
+            assert_eq!(parsed.to_string(), name);
+            assert_eq!(
+                name.to_lowercase().parse::<Mnemonic>().unwrap().to_string(),
+                name
+            );
+        }
+        assert_eq!(Mnemonic::LDA.to_string(), "LDA");
+    }
+
+    #[test]
+    fn test_mnemonic_parse_error() {
+        let err = "XYZ".parse::<Mnemonic>().unwrap_err();
+        assert_eq!(err, ParseMnemonicError("XYZ".to_string()));
+        assert_eq!(err.to_string(), "unknown mnemonic: XYZ");
+        assert!("".parse::<Mnemonic>().is_err());
+    }
+
+    #[test]
+    fn test_find_opcode() {
+        let lda = find_opcode(Mnemonic::LDA, AddressingMode::Immediate).unwrap();
+        assert_eq!(lda.opcode(), 0xa9);
+        assert!(find_opcode(Mnemonic::TAX, AddressingMode::Immediate).is_none());
+
+        let nop = find_opcode(Mnemonic::NOP, AddressingMode::NoneAddressing).unwrap();
+        assert_eq!(nop.opcode(), 0xea);
+    }
+
+    #[test]
+    fn test_operand_bytes() {
+        use AddressingMode::*;
+
+        let sizes = [
+            (Immediate, 1),
+            (ZeroPage, 1),
+            (ZeroPageX, 1),
+            (ZeroPageY, 1),
+            (Absolute, 2),
+            (AbsoluteX, 2),
+            (AbsoluteY, 2),
+            (Indirect, 2),
+            (IndirectX, 1),
+            (IndirectY, 1),
+            (Relative, 1),
+            (Accumulator, 0),
+            (NoneAddressing, 0),
+        ];
+        for (mode, size) in sizes.iter() {
+            assert_eq!(mode.operand_bytes(), *size, "{:?}", mode);
+        }
+
+        for op in CPU_OPCODES {
+            assert_eq!(
+                op.bytes(),
+                1 + op.addr_mode().operand_bytes(),
+                "{:#04x}",
+                op.opcode()
+            );
+        }
+    }
+
+    /// Base cycle counts indexed by opcode, 0 for opcodes this CPU does not
+    /// implement. Branches add 1 when taken and 1 more on a page cross, and
+    /// reads (not stores or read-modify-writes) through abs,X, abs,Y and
+    /// (ind),Y add 1 on a page cross; those penalties are not included here.
+    #[rustfmt::skip]
+    const REFERENCE_CYCLES: [u8; 256] = [
+    //  0  1  2  3  4  5  6  7  8  9  A  B  C  D  E  F
+        7, 6, 0, 8, 3, 3, 5, 5, 3, 2, 2, 0, 4, 4, 6, 6, // 0
+        2, 5, 0, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 7, 7, // 1
+        0, 6, 0, 8, 3, 3, 5, 5, 4, 2, 2, 0, 4, 4, 6, 6, // 2
+        2, 5, 0, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 7, 7, // 3
+        6, 6, 0, 8, 3, 3, 5, 5, 3, 2, 2, 0, 3, 4, 6, 6, // 4
+        2, 5, 0, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 7, 7, // 5
+        0, 6, 0, 8, 3, 3, 5, 5, 4, 2, 2, 0, 5, 4, 6, 6, // 6
+        2, 5, 0, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 7, 7, // 7
+        2, 6, 2, 6, 3, 3, 3, 3, 2, 2, 2, 0, 4, 4, 4, 4, // 8
+        2, 6, 0, 0, 4, 4, 4, 4, 2, 5, 2, 0, 0, 5, 0, 0, // 9
+        2, 6, 2, 6, 3, 3, 3, 3, 2, 2, 2, 0, 4, 4, 4, 4, // A
+        2, 5, 0, 5, 4, 4, 4, 4, 2, 4, 2, 0, 4, 4, 4, 4, // B
+        2, 6, 2, 8, 3, 3, 5, 5, 2, 2, 2, 0, 4, 4, 6, 6, // C
+        2, 5, 0, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 7, 7, // D
+        2, 6, 2, 8, 3, 3, 5, 5, 2, 2, 2, 0, 4, 4, 6, 6, // E
+        2, 5, 0, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 7, 7, // F
+    ];
+
+    #[test]
+    fn test_opcode_cycles_match_reference() {
+        for op in CPU_OPCODES {
+            assert_eq!(
+                op.cycles(),
+                REFERENCE_CYCLES[op.opcode() as usize],
+                "{:#04x}",
+                op.opcode()
+            );
+        }
+
+        let implemented = REFERENCE_CYCLES.iter().filter(|&&cycles| cycles != 0);
+        assert_eq!(implemented.count(), CPU_OPCODES.len());
+    }
+
+    #[test]
+    fn test_jmp_forward_lands_on_target() {
+        let mut cpu = CPU::new();
+        cpu.load(vec![0x4c, 0x05, 0x80, 0xe8, 0xe8, 0x00]).unwrap();
+        cpu.reset();
+
+        cpu.step();
+        assert_eq!(cpu.program_counter, 0x8005);
+        assert_eq!(cpu.register_x, 0x00);
+    }
+
+    #[test]
+    fn test_jmp_to_next_byte_does_not_advance() {
+        // The target is the byte right after the JMP opcode, where the
+        // program counter already points before the jump.
+        let mut cpu = CPU::new();
+        cpu.load(vec![0x4c, 0x01, 0x80, 0x00]).unwrap();
+        cpu.reset();
+
+        cpu.step();
+        assert_eq!(cpu.program_counter, 0x8001);
+    }
+
+    #[test]
+    fn test_implemented_opcodes() {
+        let opcodes = implemented_opcodes();
+        assert_eq!(opcodes.len(), CPU_OPCODES.len());
+        assert!(opcodes.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(opcodes.contains(&0xa9));
+        assert!(!opcodes.contains(&0x02));
+
+        assert_eq!(coverage(), CPU_OPCODES.len() as f32 / 256.0);
+    }
+
+    #[test]
+    fn test_validate_opcode_table() {
+        assert_eq!(validate_opcode_table(), Ok(()));
+    }
+
+    #[test]
+    fn test_trap_jmp_to_self() {
+        let mut cpu = CPU::new();
+        cpu.load(vec![0xe8, 0x4c, 0x01, 0x80]).unwrap();
+        cpu.reset();
+        cpu.trap_self_loops = true;
+
+        assert_eq!(cpu.run(), StopReason::Trap(0x8001));
+        assert_eq!(cpu.program_counter, 0x8001);
+        assert_eq!(cpu.register_x, 0x01);
+    }
+
+    #[test]
+    fn test_trap_branch_to_self() {
+        let mut cpu = CPU::new();
+        cpu.load(vec![0xa2, 0x00, 0xf0, 0xfe]).unwrap();
+        cpu.reset();
+        cpu.trap_self_loops = true;
+
+        assert_eq!(cpu.run(), StopReason::Trap(0x8002));
+    }
+
+    #[test]
+    fn test_trap_disabled_by_default() {
+        let mut cpu = CPU::new();
+        cpu.load(vec![0x4c, 0x00, 0x80]).unwrap();
+        cpu.reset();
+
+        assert_eq!(cpu.run_for(30), StopReason::CyclesExhausted);
     }
 }
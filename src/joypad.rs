@@ -0,0 +1,203 @@
+use crate::bus::{Bus, FlatMemory};
+
+const JOYPAD_1: u16 = 0x4016;
+
+/// Buttons in the order the controller shifts them out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Button {
+    A,
+    B,
+    Select,
+    Start,
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+/// A standard controller. Writing 1 then 0 to the strobe latches the
+/// buttons, after which each read returns the next one, starting with A.
+#[derive(Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Joypad {
+    buttons: u8,
+    strobe: bool,
+    index: u8,
+}
+
+impl Joypad {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_button_pressed(&mut self, button: Button, pressed: bool) {
+        let mask = 1 << button as u8;
+        if pressed {
+            self.buttons |= mask;
+        } else {
+            self.buttons &= !mask;
+        }
+    }
+
+    pub fn write(&mut self, data: u8) {
+        self.strobe = data & 1 != 0;
+        if self.strobe {
+            self.index = 0;
+        }
+    }
+
+    /// Returns the current button and moves on to the next one, unless the
+    /// strobe is held high. Reads past Right return 1 like the real pad.
+    pub fn read(&mut self) -> u8 {
+        let data = self.peek();
+        if !self.strobe && self.index < 8 {
+            self.index += 1;
+        }
+        data
+    }
+
+    pub fn peek(&self) -> u8 {
+        if self.index >= 8 {
+            return 1;
+        }
+        (self.buttons >> self.index) & 1
+    }
+}
+
+/// Puts a `Joypad` at 0x4016 in front of another bus.
+#[derive(Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct JoypadBus<B: Bus = FlatMemory> {
+    pub joypad: Joypad,
+    pub inner: B,
+}
+
+impl<B: Bus> JoypadBus<B> {
+    pub fn new(inner: B) -> Self {
+        Self {
+            joypad: Joypad::new(),
+            inner,
+        }
+    }
+}
+
+impl<B: Bus> Bus for JoypadBus<B> {
+    fn read(&mut self, addr: u16) -> u8 {
+        match addr {
+            JOYPAD_1 => self.joypad.read(),
+            _ => self.inner.read(addr),
+        }
+    }
+
+    fn write(&mut self, addr: u16, data: u8) {
+        match addr {
+            JOYPAD_1 => self.joypad.write(data),
+            _ => self.inner.write(addr, data),
+        }
+    }
+
+    fn peek(&self, addr: u16) -> u8 {
+        match addr {
+            JOYPAD_1 => self.joypad.peek(),
+            _ => self.inner.peek(addr),
+        }
+    }
+
+    /// Pokes at 0x4016 are dropped, leaving the strobe alone.
+    fn poke(&mut self, addr: u16, data: u8) {
+        match addr {
+            JOYPAD_1 => {}
+            _ => self.inner.poke(addr, data),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::asm::assemble;
+    use crate::cpu::CPU;
+
+    #[test]
+    fn test_read_order() {
+        let mut joypad = Joypad::new();
+        joypad.set_button_pressed(Button::A, true);
+        joypad.set_button_pressed(Button::Start, true);
+        joypad.set_button_pressed(Button::Right, true);
+        joypad.set_button_pressed(Button::Start, false);
+        joypad.set_button_pressed(Button::Up, true);
+
+        joypad.write(1);
+        joypad.write(0);
+        let bits: Vec<u8> = (0..10).map(|_| joypad.read()).collect();
+        assert_eq!(bits, vec![1, 0, 0, 0, 1, 0, 0, 1, 1, 1]);
+    }
+
+    #[test]
+    fn test_strobe_held_repeats_a() {
+        let mut joypad = Joypad::new();
+        joypad.set_button_pressed(Button::B, true);
+
+        joypad.write(1);
+        assert_eq!(joypad.read(), 0);
+        assert_eq!(joypad.read(), 0);
+
+        joypad.write(0);
+        assert_eq!(joypad.read(), 0);
+        assert_eq!(joypad.read(), 1);
+    }
+
+    #[test]
+    fn test_poke_skips_strobe() {
+        let mut bus = JoypadBus::new(FlatMemory::new());
+        bus.joypad.set_button_pressed(Button::B, true);
+        bus.write(JOYPAD_1, 1);
+        bus.write(JOYPAD_1, 0);
+        bus.read(JOYPAD_1);
+
+        bus.poke(JOYPAD_1, 1);
+        bus.poke(0x0010, 0x42);
+        assert_eq!(bus.read(JOYPAD_1), 1);
+        assert_eq!(bus.peek(0x0010), 0x42);
+    }
+
+    #[test]
+    fn test_loaders_skip_strobe() {
+        let mut cpu = CPU::with_bus(JoypadBus::new(FlatMemory::new()));
+        cpu.bus.joypad.set_button_pressed(Button::B, true);
+        cpu.bus.write(JOYPAD_1, 1);
+        cpu.bus.write(JOYPAD_1, 0);
+        cpu.bus.read(JOYPAD_1);
+
+        cpu.load_at(vec![0x01], JOYPAD_1).unwrap();
+        cpu.mem_write(JOYPAD_1, 0x01);
+        cpu.reset_full();
+        assert_eq!(cpu.bus.read(JOYPAD_1), 1);
+    }
+
+    #[test]
+    fn test_cpu_reads_joypad() {
+        let program = assemble(
+            "LDA #1
+             STA $4016
+             LDA #0
+             STA $4016
+             LDX #0
+             LDA $4016
+             STA $00,X
+             INX
+             CPX #8
+             BNE $800c
+             BRK",
+        )
+        .unwrap();
+
+        let mut cpu = CPU::with_bus(JoypadBus::new(FlatMemory::new()));
+        cpu.bus.joypad.set_button_pressed(Button::B, true);
+        cpu.bus.joypad.set_button_pressed(Button::Select, true);
+        cpu.bus.joypad.set_button_pressed(Button::Left, true);
+        cpu.load_and_run(program);
+
+        assert_eq!(cpu.dump(0x00, 8), vec![0, 1, 1, 0, 0, 0, 1, 0]);
+    }
+}
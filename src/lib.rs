@@ -1 +1,13 @@
+//! Builds without `std` when the default `std` feature is disabled. A global
+//! allocator is still required.
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
+pub mod asm;
+pub mod bus;
 pub mod cpu;
+pub mod joypad;
+pub mod rom;
+#[cfg(feature = "wasm")]
+pub mod wasm;
@@ -0,0 +1,143 @@
+use alloc::vec::Vec;
+use core::fmt;
+
+const NES_TAG: [u8; 4] = [0x4e, 0x45, 0x53, 0x1a];
+const HEADER_SIZE: usize = 16;
+const TRAINER_SIZE: usize = 512;
+
+pub const PRG_ROM_PAGE_SIZE: usize = 0x4000;
+pub const CHR_ROM_PAGE_SIZE: usize = 0x2000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RomError {
+    InvalidMagic,
+    Truncated { expected: usize, actual: usize },
+    UnsupportedMapper(u8),
+    UnsupportedPrgSize(usize),
+}
+
+impl fmt::Display for RomError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RomError::InvalidMagic => write!(f, "file is not in iNES format"),
+            RomError::Truncated { expected, actual } => write!(
+                f,
+                "rom is truncated: expected {} bytes, got {}",
+                expected, actual
+            ),
+            RomError::UnsupportedMapper(mapper) => write!(f, "mapper {} is not supported", mapper),
+            RomError::UnsupportedPrgSize(size) => {
+                write!(f, "PRG-ROM of {} bytes is not supported", size)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for RomError {}
+
+#[derive(Debug)]
+pub struct Rom {
+    pub prg_rom: Vec<u8>,
+    pub chr_rom: Vec<u8>,
+    pub mapper: u8,
+}
+
+impl Rom {
+    pub fn new(data: &[u8]) -> Result<Self, RomError> {
+        if data.len() < HEADER_SIZE || data[0..4] != NES_TAG {
+            return Err(RomError::InvalidMagic);
+        }
+
+        let mapper = (data[7] & 0b1111_0000) | (data[6] >> 4);
+        if mapper != 0 {
+            return Err(RomError::UnsupportedMapper(mapper));
+        }
+
+        let prg_rom_size = data[4] as usize * PRG_ROM_PAGE_SIZE;
+        let chr_rom_size = data[5] as usize * CHR_ROM_PAGE_SIZE;
+        if prg_rom_size != PRG_ROM_PAGE_SIZE && prg_rom_size != 2 * PRG_ROM_PAGE_SIZE {
+            return Err(RomError::UnsupportedPrgSize(prg_rom_size));
+        }
+
+        let has_trainer = data[6] & 0b0000_0100 != 0;
+        let prg_rom_start = HEADER_SIZE + if has_trainer { TRAINER_SIZE } else { 0 };
+        let chr_rom_start = prg_rom_start + prg_rom_size;
+        let expected = chr_rom_start + chr_rom_size;
+        if data.len() < expected {
+            return Err(RomError::Truncated {
+                expected,
+                actual: data.len(),
+            });
+        }
+
+        Ok(Self {
+            prg_rom: data[prg_rom_start..chr_rom_start].to_vec(),
+            chr_rom: data[chr_rom_start..expected].to_vec(),
+            mapper,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn header(prg_banks: u8, chr_banks: u8, flags6: u8, flags7: u8) -> Vec<u8> {
+        let mut data = vec![0x4e, 0x45, 0x53, 0x1a, prg_banks, chr_banks, flags6, flags7];
+        data.resize(HEADER_SIZE, 0);
+        data
+    }
+
+    #[test]
+    fn test_parse_rom() {
+        let mut data = header(1, 1, 0, 0);
+        data.extend(vec![0x11; PRG_ROM_PAGE_SIZE]);
+        data.extend(vec![0x22; CHR_ROM_PAGE_SIZE]);
+
+        let rom = Rom::new(&data).unwrap();
+        assert_eq!(rom.prg_rom, vec![0x11; PRG_ROM_PAGE_SIZE]);
+        assert_eq!(rom.chr_rom, vec![0x22; CHR_ROM_PAGE_SIZE]);
+        assert_eq!(rom.mapper, 0);
+    }
+
+    #[test]
+    fn test_parse_rom_with_trainer() {
+        let mut data = header(1, 0, 0b0000_0100, 0);
+        data.extend(vec![0xff; TRAINER_SIZE]);
+        data.extend(vec![0x11; PRG_ROM_PAGE_SIZE]);
+
+        let rom = Rom::new(&data).unwrap();
+        assert_eq!(rom.prg_rom, vec![0x11; PRG_ROM_PAGE_SIZE]);
+    }
+
+    #[test]
+    fn test_invalid_magic() {
+        let mut data = header(1, 0, 0, 0);
+        data[3] = 0x00;
+        assert_eq!(Rom::new(&data).unwrap_err(), RomError::InvalidMagic);
+        assert_eq!(Rom::new(&[0x4e, 0x45]).unwrap_err(), RomError::InvalidMagic);
+    }
+
+    #[test]
+    fn test_unsupported_mapper() {
+        let data = header(1, 0, 0x10, 0x20);
+        assert_eq!(
+            Rom::new(&data).unwrap_err(),
+            RomError::UnsupportedMapper(0x21)
+        );
+    }
+
+    #[test]
+    fn test_truncated() {
+        let mut data = header(2, 0, 0, 0);
+        data.extend(vec![0x11; PRG_ROM_PAGE_SIZE]);
+        assert_eq!(
+            Rom::new(&data).unwrap_err(),
+            RomError::Truncated {
+                expected: HEADER_SIZE + 2 * PRG_ROM_PAGE_SIZE,
+                actual: HEADER_SIZE + PRG_ROM_PAGE_SIZE,
+            }
+        );
+    }
+}
@@ -0,0 +1,99 @@
+use crate::cpu::CPU;
+use wasm_bindgen::prelude::*;
+
+/// A thin JavaScript-facing wrapper around `CPU`.
+#[wasm_bindgen]
+pub struct WasmCpu {
+    cpu: CPU,
+}
+
+#[wasm_bindgen]
+impl WasmCpu {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        Self { cpu: CPU::new() }
+    }
+
+    /// Loads a program at 0x8000 and resets the CPU.
+    pub fn load(&mut self, program: &[u8]) -> Result<(), String> {
+        self.cpu
+            .load(program.to_vec())
+            .map_err(|err| err.to_string())?;
+        self.cpu.reset();
+        Ok(())
+    }
+
+    pub fn reset(&mut self) {
+        self.cpu.reset();
+    }
+
+    /// Executes one instruction and returns whether the CPU stopped.
+    pub fn step(&mut self) -> bool {
+        self.cpu.step().stop.is_some()
+    }
+
+    /// Copies `len` bytes of memory starting at `start`.
+    pub fn memory(&self, start: u16, len: usize) -> Vec<u8> {
+        self.cpu.dump(start, len)
+    }
+
+    pub fn register_a(&self) -> u8 {
+        self.cpu.register_a
+    }
+
+    pub fn register_x(&self) -> u8 {
+        self.cpu.register_x
+    }
+
+    pub fn register_y(&self) -> u8 {
+        self.cpu.register_y
+    }
+
+    pub fn register_sp(&self) -> u8 {
+        self.cpu.register_sp
+    }
+
+    pub fn status(&self) -> u8 {
+        self.cpu.status
+    }
+
+    pub fn program_counter(&self) -> u16 {
+        self.cpu.program_counter
+    }
+
+    pub fn total_cycles(&self) -> usize {
+        self.cpu.total_cycles()
+    }
+}
+
+impl Default for WasmCpu {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_step() {
+        let mut cpu = WasmCpu::new();
+        cpu.load(&[0xa9, 0x42, 0x85, 0x10, 0x00]).unwrap();
+
+        assert!(!cpu.step());
+        assert_eq!(cpu.register_a(), 0x42);
+        assert_eq!(cpu.program_counter(), 0x8002);
+        assert_eq!(cpu.total_cycles(), 2);
+
+        assert!(!cpu.step());
+        assert_eq!(cpu.memory(0x10, 1), vec![0x42]);
+        assert!(cpu.step());
+    }
+
+    #[test]
+    fn test_load_too_large() {
+        let mut cpu = WasmCpu::new();
+        assert!(cpu.load(&[0xea; 0x8001]).is_err());
+    }
+}
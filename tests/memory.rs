@@ -0,0 +1,24 @@
+use cubize::cpu::{StopReason, CPU};
+
+#[test]
+fn test_mem_write_is_visible_to_program() {
+    let mut cpu = CPU::new();
+    cpu.mem_write(0x10, 0x42);
+    // LDA $10; STA $0200; BRK
+    cpu.load(vec![0xa5, 0x10, 0x8d, 0x00, 0x02, 0x00]).unwrap();
+    cpu.reset();
+
+    assert_eq!(cpu.run(), StopReason::Break);
+    assert_eq!(cpu.register_a, 0x42);
+    assert_eq!(cpu.mem_read(0x0200), 0x42);
+}
+
+#[test]
+fn test_mem_u16_is_little_endian() {
+    let mut cpu = CPU::new();
+    cpu.mem_write_u16(0x1234, 0xbeef);
+
+    assert_eq!(cpu.mem_read(0x1234), 0xef);
+    assert_eq!(cpu.mem_read(0x1235), 0xbe);
+    assert_eq!(cpu.mem_read_u16(0x1234), 0xbeef);
+}